@@ -10,6 +10,7 @@ const MIDI_EXT: &[&str] = &["mid", "midi", "rmi"];
 
 const PARSERS: &[(&str, fn(&Path) -> Result<usize, String>)] = &[
     (&"midly", parse_midly),
+    (&"midly-scan", scan_midly),
     (&"nom-midi", parse_nom),
     (&"rimd", parse_rimd),
     (&"augmented-midi", parse_augmented_midi),
@@ -21,6 +22,19 @@ fn parse_midly(path: &Path) -> Result<usize, String> {
     Ok(smf.tracks.len())
 }
 
+fn scan_midly(path: &Path) -> Result<usize, String> {
+    let data = fs::read(path).map_err(|err| format!("{}", err))?;
+    let (_header, tracks) = midly::parse(&data).map_err(|err| format!("{}", err))?;
+    let mut track_count = 0;
+    for track in tracks {
+        let track = track.map_err(|err| format!("{}", err))?;
+        // Look for the sustain pedal without decoding any events
+        let _ = track.contains_controller(64.into());
+        track_count += 1;
+    }
+    Ok(track_count)
+}
+
 fn parse_nom(path: &Path) -> Result<usize, String> {
     let data = fs::read(path).map_err(|err| format!("{}", err))?;
     let smf = nom_midi::parser::parse_smf(&data)
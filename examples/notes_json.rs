@@ -0,0 +1,69 @@
+//! Print the notes of a MIDI file as a JSON array.
+//!
+//! Usage: `cargo run --example notes_json -- path/to/file.mid`
+//!
+//! Each note is printed as an object with its start time and duration in seconds, along with its
+//! channel, key and velocity.
+//! All tracks are played simultaneously, following the tempo map of the file.
+
+use midly::{edit, iter, num::u24, Smf};
+use serde::Serialize;
+use std::{env, error::Error, fs, io};
+
+/// The default tempo of 120 beats per minute, in microseconds per beat.
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// The JSON representation of a note.
+#[derive(Serialize)]
+struct JsonNote {
+    start_sec: f64,
+    dur_sec: f64,
+    channel: u8,
+    key: u8,
+    velocity: u8,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("usage: notes_json <file.mid>")?;
+    let data = fs::read(path)?;
+    let smf = Smf::parse(&data)?;
+    let timing = smf.header.timing;
+
+    // The time of each tempo change in microseconds, as `(tick, micros, tempo)`
+    let mut tempo_map = vec![(0, 0, u24::new(DEFAULT_TEMPO))];
+    for (tick, tempo) in smf.global_tempo_map() {
+        let &(last_tick, last_micros, last_tempo) = tempo_map.last().unwrap();
+        let micros = last_micros
+            + timing
+                .ticks_to_micros(tick - last_tick, last_tempo)
+                .ok_or("invalid timing")?;
+        tempo_map.push((tick, micros, tempo));
+    }
+    // Convert from the last tempo change, so that rounding errors do not accumulate
+    let to_secs = |tick: u64| -> Result<f64, &'static str> {
+        let idx = tempo_map.partition_point(|&(change, _, _)| change <= tick) - 1;
+        let (change, micros, tempo) = tempo_map[idx];
+        let since = timing
+            .ticks_to_micros(tick - change, tempo)
+            .ok_or("invalid timing")?;
+        Ok((micros + since) as f64 / 1e6)
+    };
+
+    // Merge all tracks, so that notes from all tracks are listed in order
+    let merged = edit::merge(&smf.tracks);
+    let notes = iter::notes(&merged)
+        .map(|note| {
+            let start = to_secs(note.start)?;
+            Ok(JsonNote {
+                start_sec: start,
+                dur_sec: to_secs(note.end)? - start,
+                channel: note.channel.as_int(),
+                key: note.key.as_int(),
+                velocity: note.vel.as_int(),
+            })
+        })
+        .collect::<Result<Vec<_>, &'static str>>()?;
+    serde_json::to_writer_pretty(io::stdout().lock(), &notes)?;
+    println!();
+    Ok(())
+}
@@ -0,0 +1,465 @@
+//! Best-effort analysis of the musical content of a file.
+//!
+//! Nothing here is needed to parse or write files, these are just convenient summaries that
+//! build on top of the parsed events.
+
+#![cfg(feature = "alloc")]
+
+use crate::{
+    edit,
+    event::{MetaMessage, MidiMessage, TrackEventKind},
+    internal::*,
+    primitive::Format,
+    smf::{Smf, Track},
+};
+
+/// The channel that General MIDI reserves for percussion (channel 10, counting from 1).
+const PERCUSSION_CHANNEL: u8 = 9;
+
+/// The Krumhansl-Kessler key profiles, measuring how well each pitch class fits a major or minor
+/// key, starting from the tonic.
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// A rough classification of the contents of a file, as guessed by
+/// [`Smf::classify`](struct.Smf.html#method.classify).
+///
+/// This is a heuristic hint, useful to organize a library of files, but it can easily be wrong.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum FileClass {
+    /// The file plays no notes at all.
+    Empty,
+    /// Only a percussion channel is used, and the same few drum sounds are played over and over.
+    DrumLoop,
+    /// A single non-percussion channel is used, and notes never overlap.
+    SingleMelody,
+    /// A single channel is used, but with overlapping notes (chords) or with varied percussion.
+    SingleInstrument,
+    /// Several channels are used, as in a full arrangement.
+    Multitrack,
+}
+
+impl<'a> Smf<'a> {
+    /// Count the number of notes played on each channel, across all tracks.
+    ///
+    /// Only `NoteOn` events with a nonzero velocity are counted.
+    pub fn note_histogram(&self) -> [usize; 16] {
+        let mut histogram = [0; 16];
+        for ev in self.tracks.iter().flatten() {
+            if let TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn { vel, .. },
+            } = ev.kind
+            {
+                if vel > 0 {
+                    histogram[channel.as_int() as usize] += 1;
+                }
+            }
+        }
+        histogram
+    }
+
+    /// Guess what kind of musical content this file holds, based on channel usage and how notes
+    /// are played.
+    ///
+    /// See [`FileClass`](enum.FileClass.html) for the possible outcomes.
+    pub fn classify(&self) -> FileClass {
+        let histogram = self.note_histogram();
+        let mut used = (0..16u8).filter(|&ch| histogram[ch as usize] > 0);
+        let channel = match (used.next(), used.next()) {
+            (None, _) => return FileClass::Empty,
+            (Some(channel), None) => channel,
+            (Some(_), Some(_)) => return FileClass::Multitrack,
+        };
+        let merged = edit::merge(&self.tracks);
+        if self.drum_channels() & 1 << channel != 0 {
+            //A loop repeats each of its sounds several times
+            let mut keys = [false; 128];
+            for ev in merged.iter() {
+                if let TrackEventKind::Midi {
+                    message: MidiMessage::NoteOn { key, vel },
+                    ..
+                } = ev.kind
+                {
+                    if vel > 0 {
+                        keys[key.as_int() as usize] = true;
+                    }
+                }
+            }
+            let distinct = keys.iter().filter(|&&played| played).count();
+            if histogram[channel as usize] >= 4 * distinct {
+                return FileClass::DrumLoop;
+            }
+            return FileClass::SingleInstrument;
+        }
+        //Find out whether notes ever overlap
+        let mut playing = [0u32; 128];
+        let mut polyphony = 0u32;
+        for ev in merged.iter() {
+            if let TrackEventKind::Midi { message, .. } = ev.kind {
+                let (key, on) = match message {
+                    MidiMessage::NoteOn { key, vel } => (key, vel > 0),
+                    MidiMessage::NoteOff { key, .. } => (key, false),
+                    _ => continue,
+                };
+                let count = &mut playing[key.as_int() as usize];
+                if on {
+                    *count += 1;
+                    polyphony += 1;
+                    if polyphony > 1 {
+                        return FileClass::SingleInstrument;
+                    }
+                } else if *count > 0 {
+                    *count -= 1;
+                    polyphony -= 1;
+                }
+            }
+        }
+        FileClass::SingleMelody
+    }
+
+    /// Guess whether the tracks of this file are independent sequences, as in a `Sequential`
+    /// file, rather than parts played simultaneously, as in a `Parallel` file.
+    ///
+    /// `Sequential` files are rare, and files are often mislabeled, so the format field alone is
+    /// not reliable.
+    /// The heuristic ignores the format field, and considers the tracks to be independent if all
+    /// of the following hold:
+    ///
+    /// - At least two tracks play notes.
+    /// - The first track plays notes, so it is not a conductor track holding only tempo and other
+    ///   global meta events.
+    /// - Some channel plays notes in more than one track.
+    ///   Simultaneous tracks usually play on separate channels, while independent patterns tend
+    ///   to reuse the same ones.
+    ///
+    /// This can easily be wrong: for example, a `Parallel` file with a drum kit split across
+    /// several tracks on the same channel looks sequential, and independent patterns that play on
+    /// separate channels look parallel.
+    pub fn is_truly_sequential(&self) -> bool {
+        let masks = self
+            .tracks
+            .iter()
+            .map(|track| {
+                track.iter().fold(0u16, |mask, ev| match ev.kind {
+                    TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOn { vel, .. },
+                    } if vel > 0 => mask | 1 << channel.as_int(),
+                    _ => mask,
+                })
+            })
+            .collect::<Vec<_>>();
+        let playing = masks.iter().filter(|&&mask| mask != 0).count();
+        let mut seen = 0;
+        let mut shared = false;
+        for &mask in masks.iter() {
+            shared |= seen & mask != 0;
+            seen |= mask;
+        }
+        playing >= 2 && masks[0] != 0 && shared
+    }
+
+    /// Correct the format field of the header if it is obviously wrong, returning whether it was
+    /// changed.
+    ///
+    /// A `SingleTrack` file with several tracks becomes `Sequential` or `Parallel`, and a
+    /// `Sequential` file becomes `Parallel`, depending on the guess of
+    /// [`is_truly_sequential`](#method.is_truly_sequential).
+    /// `Parallel` files are left alone, since mislabeled files are usually `Parallel` files marked
+    /// otherwise, and the guess is not reliable enough to overrule them.
+    pub fn normalize_format(&mut self) -> bool {
+        let format = match self.header.format {
+            Format::SingleTrack if self.tracks.len() <= 1 => return false,
+            Format::Parallel => return false,
+            Format::SingleTrack | Format::Sequential => {
+                if self.is_truly_sequential() {
+                    Format::Sequential
+                } else {
+                    Format::Parallel
+                }
+            }
+        };
+        let changed = format != self.header.format;
+        self.header.format = format;
+        changed
+    }
+
+    /// Get the conductor track of a `Parallel` file, that is, its first track.
+    ///
+    /// By convention, the conductor track holds the global tempo and time signature changes of
+    /// the file.
+    /// Returns `None` for files of any other format, or if the file has no tracks.
+    pub fn conductor_track(&self) -> Option<&Track<'a>> {
+        match self.header.format {
+            Format::Parallel => self.tracks.first(),
+            Format::SingleTrack | Format::Sequential => None,
+        }
+    }
+
+    /// Collect the tempo changes of the file, as `(tick, tempo)` pairs sorted by absolute tick.
+    ///
+    /// If the file has a [conductor track](#method.conductor_track) with any `Tempo` events, only
+    /// the conductor track is considered, as most sequencers do.
+    /// Otherwise, the `Tempo` events of all tracks are collected, with simultaneous changes
+    /// sorted by track order.
+    /// Note that the tracks of `Sequential` files are independent, so their ticks are
+    /// relative to the start of each track.
+    pub fn global_tempo_map(&self) -> Vec<(u64, u24)> {
+        conductor_map(self, |meta| match meta {
+            MetaMessage::Tempo(tempo) => Some(tempo),
+            _ => None,
+        })
+    }
+
+    /// Collect the time signature changes of the file, as `(tick, signature)` pairs sorted by
+    /// absolute tick.
+    ///
+    /// Each signature holds the fields of the `TimeSignature` meta message, in the same order:
+    /// numerator, denominator (as a power of two), MIDI clocks per click and 32nd notes per
+    /// quarter.
+    /// Changes are looked up in the [conductor track](#method.conductor_track) first, in the same
+    /// way as [`global_tempo_map`](#method.global_tempo_map) does.
+    pub fn global_time_signature_map(&self) -> Vec<(u64, (u8, u8, u8, u8))> {
+        conductor_map(self, |meta| match meta {
+            MetaMessage::TimeSignature(num, den, clocks, notes) => Some((num, den, clocks, notes)),
+            _ => None,
+        })
+    }
+
+    /// Collect the tempo changes of the file as `(tick, bpm)` pairs, in beats (quarter notes) per
+    /// minute rather than microseconds per beat.
+    ///
+    /// The changes are the same ones, in the same order, as the ones in
+    /// [`global_tempo_map`](#method.global_tempo_map), which is the data needed to display a
+    /// tempo curve.
+    /// Note that a tempo of 0 microseconds per beat converts to an infinite BPM.
+    pub fn tempo_changes(&self) -> Vec<(u64, f64)> {
+        self.global_tempo_map()
+            .into_iter()
+            .map(|(tick, tempo)| (tick, 60_000_000.0 / tempo.as_int() as f64))
+            .collect()
+    }
+
+    /// Estimate the key of the file, as the amount of sharps (if positive) or flats (if negative)
+    /// in its key signature, along with whether the key is minor.
+    ///
+    /// This is the same representation used by the `KeySignature` meta message.
+    /// If the file contains any `KeySignature` events, the earliest one is used.
+    /// Otherwise, the key is guessed with the Krumhansl-Schmuckler algorithm: the amount of notes
+    /// played of each pitch class is compared against the typical profile of all 24 major and
+    /// minor keys, and the best match is chosen.
+    /// Percussion channels, as found by [`drum_channels`](#method.drum_channels), are ignored,
+    /// since drums have no pitch.
+    ///
+    /// Guessed keys use sharps rather than flats for F# major and D# minor.
+    /// Returns `None` if the file has no `KeySignature` events and plays no pitched notes.
+    pub fn estimate_key(&self) -> Option<(i8, bool)> {
+        let signature = self
+            .iter_all()
+            .filter_map(|(_, tick, ev)| match ev.kind {
+                TrackEventKind::Meta(MetaMessage::KeySignature(sharps, minor)) => {
+                    Some((tick, (sharps, minor)))
+                }
+                _ => None,
+            })
+            .min_by_key(|&(tick, _)| tick);
+        if let Some((_, key)) = signature {
+            return Some(key);
+        }
+        let drums = self.drum_channels();
+        let mut histogram = [0.0; 12];
+        for ev in self.tracks.iter().flatten() {
+            if let TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn { key, vel },
+            } = ev.kind
+            {
+                if vel > 0 && drums & 1 << channel.as_int() == 0 {
+                    histogram[key.as_int() as usize % 12] += 1.0;
+                }
+            }
+        }
+        let total = histogram.iter().sum::<f64>();
+        if total == 0.0 {
+            return None;
+        }
+        //Correlate the histogram with every key profile.
+        //The spread of the histogram is the same for every key, so it can be left out, and
+        //comparing `cov * |cov| / var` avoids square roots while keeping the same ordering as
+        //the correlation coefficient.
+        let mean = total / 12.0;
+        let score = |profile: &[f64; 12], tonic: usize| {
+            let profile_mean = profile.iter().sum::<f64>() / 12.0;
+            let mut cov = 0.0;
+            let mut var = 0.0;
+            for (i, &p) in profile.iter().enumerate() {
+                let p = p - profile_mean;
+                cov += (histogram[(tonic + i) % 12] - mean) * p;
+                var += p * p;
+            }
+            cov * cov.abs() / var
+        };
+        let (tonic, minor) = (0..12)
+            .flat_map(|tonic| [(tonic, false), (tonic, true)])
+            .max_by(|&(a, a_minor), &(b, b_minor)| {
+                let profile = |minor| {
+                    if minor {
+                        &MINOR_PROFILE
+                    } else {
+                        &MAJOR_PROFILE
+                    }
+                };
+                score(profile(a_minor), a)
+                    .partial_cmp(&score(profile(b_minor), b))
+                    .unwrap_or(core::cmp::Ordering::Equal)
+            })?;
+        //Walk the circle of fifths from C major to the relative major of the key
+        let major_tonic = if minor { (tonic + 3) % 12 } else { tonic };
+        let sharps = (major_tonic * 7 % 12) as i8;
+        Some((if sharps > 6 { sharps - 12 } else { sharps }, minor))
+    }
+
+    /// Find out which channels play notes as percussion, as a bitmask where bit `n` stands for
+    /// channel `n` (counting from 0).
+    ///
+    /// Only channel 10 (`9` counting from 0) plays percussion at first, but this can be changed
+    /// by messages that are followed in playback order:
+    ///
+    /// - Roland GS "use for rhythm part" SysEx messages turn any channel into a percussion
+    ///   channel or back into a melodic one.
+    /// - As in General MIDI 2, a bank select (controller 0) of `0x78` or `0x79` turns a channel
+    ///   into a percussion or a melodic channel respectively.
+    ///   Like any bank select, it only takes effect at the next program change on that channel.
+    /// - General MIDI and GS reset SysEx messages restore the initial setup.
+    ///
+    /// A channel is included in the mask only if a note is played on it while it is a percussion
+    /// channel.
+    /// Channels that are configured as percussion but never play a note are not included, so a
+    /// file that sets up its drum channels but plays no notes yet has an empty mask.
+    pub fn drum_channels(&self) -> u16 {
+        let mut drums = 1 << PERCUSSION_CHANNEL;
+        let mut used = 0;
+        // The last bank selected on each channel, which applies at the next program change
+        let mut banks = [None; 16];
+        for ev in edit::merge(&self.tracks) {
+            match ev.kind {
+                TrackEventKind::SysEx(data) => drums = sysex_drums(data, drums),
+                TrackEventKind::Midi { channel, message } => {
+                    let bit = 1 << channel.as_int();
+                    match message {
+                        MidiMessage::Controller { controller, value } if controller == 0 => {
+                            banks[channel.as_int() as usize] = Some(value.as_int());
+                        }
+                        MidiMessage::ProgramChange { .. } => {
+                            match banks[channel.as_int() as usize] {
+                                Some(0x78) => drums |= bit,
+                                Some(0x79) => drums &= !bit,
+                                _ => {}
+                            }
+                        }
+                        MidiMessage::NoteOn { vel, .. } if vel > 0 && drums & bit != 0 => {
+                            used |= bit
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        used
+    }
+
+    /// Get the absolute tick of the earliest note in the file, across all tracks.
+    ///
+    /// Only `NoteOn` events with a nonzero velocity are considered.
+    /// Returns `None` if the file plays no notes at all.
+    pub fn first_note_tick(&self) -> Option<u64> {
+        self.tracks
+            .iter()
+            .filter_map(|track| {
+                let mut tick = 0;
+                track.iter().find_map(|ev| {
+                    tick += ev.delta.as_int() as u64;
+                    match ev.kind {
+                        TrackEventKind::Midi {
+                            message: MidiMessage::NoteOn { vel, .. },
+                            ..
+                        } if vel > 0 => Some(tick),
+                        _ => None,
+                    }
+                })
+            })
+            .min()
+    }
+
+    /// Remove any silence before the first note of the file, shifting all tracks earlier by the
+    /// same amount of ticks so that they stay in sync.
+    ///
+    /// Events that happened before the first note, such as tempo or program changes, are moved
+    /// to tick 0, keeping their relative order.
+    /// If the file plays no notes, it is left untouched.
+    pub fn trim_leading_silence(&mut self) {
+        if let Some(silence) = self.first_note_tick() {
+            for track in self.tracks.iter_mut() {
+                edit::trim_start(track, silence);
+            }
+        }
+    }
+}
+
+/// Collect the meta events picked out by `f` as `(tick, value)` pairs sorted by tick, only from
+/// the conductor track if it has any of them, or from all tracks otherwise.
+fn conductor_map<'a, T>(smf: &Smf<'a>, f: impl Fn(MetaMessage<'a>) -> Option<T>) -> Vec<(u64, T)> {
+    let collect = |tracks: &[Track<'a>]| {
+        let mut map = Vec::new();
+        for track in tracks {
+            for (tick, kind) in edit::absolute_events(track) {
+                if let TrackEventKind::Meta(meta) = kind {
+                    if let Some(value) = f(meta) {
+                        map.push((tick, value));
+                    }
+                }
+            }
+        }
+        //The sort is stable, so simultaneous changes stay in track order
+        map.sort_by_key(|&(tick, _)| tick);
+        map
+    };
+    if let Some(conductor) = smf.conductor_track() {
+        let map = collect(core::slice::from_ref(conductor));
+        if !map.is_empty() {
+            return map;
+        }
+    }
+    collect(&smf.tracks)
+}
+
+/// Apply the effect of a SysEx message (without the leading `0xF0`) to a bitmask of percussion
+/// channels.
+fn sysex_drums(data: &[u8], drums: u16) -> u16 {
+    match *data {
+        //General MIDI 1 and 2 "system on"
+        [0x7E, _, 0x09, 0x01, ..] | [0x7E, _, 0x09, 0x03, ..] => 1 << PERCUSSION_CHANNEL,
+        //GS reset
+        [0x41, _, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, ..] => 1 << PERCUSSION_CHANNEL,
+        //GS "use for rhythm part", where the low nibble of the block is the part number
+        [0x41, _, 0x42, 0x12, 0x40, block @ 0x10..=0x1F, 0x15, map, ..] => {
+            let channel = match block & 0xF {
+                0 => PERCUSSION_CHANNEL,
+                part @ 1..=9 => part - 1,
+                part => part,
+            };
+            if map == 0 {
+                drums & !(1 << channel)
+            } else {
+                drums | 1 << channel
+            }
+        }
+        _ => drums,
+    }
+}
@@ -0,0 +1,440 @@
+//! A readable, line-based text representation of whole files, for debugging and snapshot tests.
+
+#![cfg(feature = "alloc")]
+
+use crate::{
+    arena::Arena,
+    event::{MetaMessage, MidiMessage, PitchBend, TrackEvent, TrackEventKind},
+    internal::*,
+    primitive::{Format, Fps, SmpteTime, Timing},
+    smf::{Header, Smf},
+};
+use core::str::{FromStr, SplitWhitespace};
+
+impl<'a> Smf<'a> {
+    /// Write a readable text representation of the file, with one event per line.
+    ///
+    /// The output is similar to the one of `midicsv`, and is meant to be diffed or stored in
+    /// snapshot tests, so its format is stable:
+    ///
+    /// ```text
+    /// header parallel tpq 96
+    /// track 0
+    ///   0 tempo 500000
+    ///   0 end_of_track
+    /// track 1
+    ///   0 track_name "Piano"
+    ///   0 note_on 0 60 64
+    ///   96 note_off 0 60 64
+    ///   96 end_of_track
+    /// ```
+    ///
+    /// The first line is the header, with the format (`single`, `parallel` or `sequential`)
+    /// followed by the timing: either `tpq` and the ticks per beat, or `timecode`, the frames per
+    /// second (`24`, `25`, `29` or `30`) and the subframes per frame.
+    /// Each track starts with a `track` line holding its index, followed by its events.
+    ///
+    /// Event lines are indented by two spaces, and start with the absolute tick of the event
+    /// within its track, followed by the event name and its arguments, all separated by single
+    /// spaces:
+    ///
+    /// - MIDI messages: `note_off`, `note_on` and `aftertouch` take the channel, key and velocity;
+    ///   `controller` takes the channel, controller and value; `program_change` takes the channel
+    ///   and program; `channel_aftertouch` takes the channel and velocity; `pitch_bend` takes the
+    ///   channel and the raw 14-bit bend value, centered at 8192.
+    ///   Channels are numbered from 0.
+    /// - `sysex` and `escape` take their data bytes, as 2-digit uppercase hexadecimal numbers.
+    /// - Meta messages: `track_number` takes its number, if any; the text meta messages
+    ///   (`text`, `copyright`, `track_name`, `instrument_name`, `lyric`, `marker`, `cue_point`,
+    ///   `program_name` and `device_name`) take a quoted string; `midi_channel`, `midi_port` and
+    ///   `tempo` take an integer; `end_of_track` takes nothing; `smpte_offset` takes the frames
+    ///   per second, hour, minute, second, frame and subframe; `time_signature` takes its four
+    ///   raw fields; `key_signature` takes the amount of sharps (negative for flats) and `major`
+    ///   or `minor`; `sequencer_specific` takes hexadecimal data bytes; and unknown meta messages
+    ///   are written as `meta`, followed by the meta type and the data bytes in hexadecimal.
+    ///
+    /// In quoted strings, printable ASCII characters are written as-is, except for `"` and `\`,
+    /// which are escaped with a backslash, while any other byte is written as `\xNN`.
+    /// This way the representation is lossless, and can be read back with
+    /// [`from_dump`](#method.from_dump).
+    ///
+    /// This method is only available with the `alloc` feature enabled.
+    pub fn dump(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        let format = match self.header.format {
+            Format::SingleTrack => "single",
+            Format::Parallel => "parallel",
+            Format::Sequential => "sequential",
+        };
+        match self.header.timing {
+            Timing::Metrical(tpq) => writeln!(out, "header {} tpq {}", format, tpq)?,
+            Timing::Timecode(fps, subframe) => writeln!(
+                out,
+                "header {} timecode {} {}",
+                format,
+                fps.as_int(),
+                subframe
+            )?,
+        }
+        for (idx, track) in self.tracks.iter().enumerate() {
+            writeln!(out, "track {}", idx)?;
+            let mut tick = 0;
+            for ev in track {
+                tick += ev.delta.as_int() as u64;
+                write!(out, "  {} ", tick)?;
+                dump_kind(&ev.kind, out)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read back a file from the text representation written by [`dump`](#method.dump).
+    ///
+    /// Together with `dump`, this allows editing files as text in a normal editor.
+    /// Since the text is not in the binary format, the byte payloads of SysEx and meta events are
+    /// decoded into the given arena, which the resulting file borrows from.
+    ///
+    /// Parsing is slightly more lenient than the written format: blank lines are ignored, and
+    /// any amount of whitespace may separate words and indent lines.
+    /// Events must appear in non-decreasing tick order within each track, and the tracks must be
+    /// numbered in order, starting from 0.
+    ///
+    /// This method is only available with the `alloc` feature enabled.
+    pub fn from_dump(text: &str, arena: &'a Arena) -> Result<Smf<'a>> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let mut header = Args::new(lines.next().ok_or(err_invalid!("missing header line"))?);
+        ensure!(
+            header.word()? == "header",
+            err_invalid!("first line is not a header line")
+        );
+        let format = match header.word()? {
+            "single" => Format::SingleTrack,
+            "parallel" => Format::Parallel,
+            "sequential" => Format::Sequential,
+            _ => bail!(err_invalid!("invalid format")),
+        };
+        let timing = match header.word()? {
+            "tpq" => Timing::Metrical(
+                u15::try_from(header.int()?).ok_or(err_invalid!("integer out of range"))?,
+            ),
+            "timecode" => Timing::Timecode(
+                Fps::from_int(header.int()?).ok_or(err_invalid!("invalid fps"))?,
+                header.int()?,
+            ),
+            _ => bail!(err_invalid!("invalid timing")),
+        };
+        header.end()?;
+        let mut smf = Smf::new(Header::new(format, timing));
+        let mut last_tick = 0;
+        for line in lines {
+            let mut args = Args::new(line);
+            let first = args.word()?;
+            if first == "track" {
+                ensure!(
+                    args.int::<usize>()? == smf.tracks.len(),
+                    err_invalid!("tracks are not numbered in order")
+                );
+                args.end()?;
+                smf.tracks.push(Vec::new());
+                last_tick = 0;
+                continue;
+            }
+            let tick = first
+                .parse::<u64>()
+                .map_err(|_| err_invalid!("invalid event tick"))?;
+            let delta = tick
+                .checked_sub(last_tick)
+                .ok_or(err_invalid!("events are not sorted by tick"))?;
+            let delta = u32::try_from(delta)
+                .ok()
+                .and_then(u28::try_from)
+                .ok_or(err_invalid!("delta time exceeds 28 bits"))?;
+            last_tick = tick;
+            let kind = parse_kind(&mut args, arena)?;
+            args.end()?;
+            smf.tracks
+                .last_mut()
+                .ok_or(err_invalid!("event outside of a track"))?
+                .push(TrackEvent { delta, kind });
+        }
+        Ok(smf)
+    }
+}
+
+/// The words of a line of a text dump.
+struct Args<'t> {
+    line: &'t str,
+    words: SplitWhitespace<'t>,
+}
+impl<'t> Args<'t> {
+    fn new(line: &'t str) -> Args<'t> {
+        Args {
+            line,
+            words: line.split_whitespace(),
+        }
+    }
+
+    fn word(&mut self) -> Result<&'t str> {
+        Ok(self.words.next().ok_or(err_invalid!("missing argument"))?)
+    }
+
+    fn int<T: FromStr>(&mut self) -> Result<T> {
+        Ok(T::from_str(self.word()?).map_err(|_| err_invalid!("invalid integer"))?)
+    }
+
+    fn u4(&mut self) -> Result<u4> {
+        Ok(u4::try_from(self.int()?).ok_or(err_invalid!("integer out of range"))?)
+    }
+
+    fn u7(&mut self) -> Result<u7> {
+        Ok(u7::try_from(self.int()?).ok_or(err_invalid!("integer out of range"))?)
+    }
+
+    fn hex<'a>(&mut self, arena: &'a Arena) -> Result<&'a [u8]> {
+        let bytes = self
+            .words
+            .by_ref()
+            .map(|word| u8::from_str_radix(word, 16))
+            .collect::<StdResult<Vec<u8>, _>>()
+            .map_err(|_| err_invalid!("invalid hexadecimal byte"))?;
+        Ok(arena.add_vec(bytes))
+    }
+
+    /// Parse a quoted string, which takes up the rest of the line.
+    fn text<'a>(&mut self, arena: &'a Arena) -> Result<&'a [u8]> {
+        let first = self
+            .words
+            .clone()
+            .next()
+            .ok_or(err_invalid!("missing text"))?;
+        //The text may contain whitespace, so take the rest of the line starting at the first word
+        let start = first.as_ptr() as usize - self.line.as_ptr() as usize;
+        let quoted = &self.line[start..];
+        ensure!(
+            quoted.len() >= 2 && quoted.starts_with('"') && quoted.ends_with('"'),
+            err_invalid!("text is not quoted")
+        );
+        let mut bytes = Vec::with_capacity(quoted.len() - 2);
+        let mut raw = &quoted.as_bytes()[1..quoted.len() - 1];
+        while let Some((&byte, rest)) = raw.split_first() {
+            raw = rest;
+            if byte != b'\\' {
+                bytes.push(byte);
+                continue;
+            }
+            match raw.split_first() {
+                Some((&(b'"' | b'\\'), rest)) => {
+                    bytes.push(raw[0]);
+                    raw = rest;
+                }
+                Some((b'x', rest)) if rest.len() >= 2 => {
+                    let hex = core::str::from_utf8(&rest[..2]).ok();
+                    let byte = hex
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                        .ok_or(err_invalid!("invalid text escape"))?;
+                    bytes.push(byte);
+                    raw = &rest[2..];
+                }
+                _ => bail!(err_invalid!("invalid text escape")),
+            }
+        }
+        self.words = "".split_whitespace();
+        Ok(arena.add_vec(bytes))
+    }
+
+    fn end(&mut self) -> Result<()> {
+        ensure!(
+            self.words.next().is_none(),
+            err_invalid!("too many arguments")
+        );
+        Ok(())
+    }
+}
+
+fn parse_kind<'a>(args: &mut Args, arena: &'a Arena) -> Result<TrackEventKind<'a>> {
+    let name = args.word()?;
+    let mut midi = |message: fn(&mut Args) -> Result<MidiMessage>| -> Result<TrackEventKind<'a>> {
+        Ok(TrackEventKind::Midi {
+            channel: args.u4()?,
+            message: message(args)?,
+        })
+    };
+    let kind = match name {
+        "note_off" => midi(|args| {
+            Ok(MidiMessage::NoteOff {
+                key: args.u7()?,
+                vel: args.u7()?,
+            })
+        })?,
+        "note_on" => midi(|args| {
+            Ok(MidiMessage::NoteOn {
+                key: args.u7()?,
+                vel: args.u7()?,
+            })
+        })?,
+        "aftertouch" => midi(|args| {
+            Ok(MidiMessage::Aftertouch {
+                key: args.u7()?,
+                vel: args.u7()?,
+            })
+        })?,
+        "controller" => midi(|args| {
+            Ok(MidiMessage::Controller {
+                controller: args.u7()?,
+                value: args.u7()?,
+            })
+        })?,
+        "program_change" => midi(|args| {
+            Ok(MidiMessage::ProgramChange {
+                program: args.u7()?,
+            })
+        })?,
+        "channel_aftertouch" => {
+            midi(|args| Ok(MidiMessage::ChannelAftertouch { vel: args.u7()? }))?
+        }
+        "pitch_bend" => midi(|args| {
+            Ok(MidiMessage::PitchBend {
+                bend: PitchBend(
+                    u14::try_from(args.int()?).ok_or(err_invalid!("integer out of range"))?,
+                ),
+            })
+        })?,
+        "sysex" => TrackEventKind::SysEx(args.hex(arena)?),
+        "escape" => TrackEventKind::Escape(args.hex(arena)?),
+        _ => TrackEventKind::Meta(match name {
+            "track_number" => MetaMessage::TrackNumber(match args.words.clone().next() {
+                Some(_) => Some(args.int()?),
+                None => None,
+            }),
+            "text" => MetaMessage::Text(args.text(arena)?),
+            "copyright" => MetaMessage::Copyright(args.text(arena)?),
+            "track_name" => MetaMessage::TrackName(args.text(arena)?),
+            "instrument_name" => MetaMessage::InstrumentName(args.text(arena)?),
+            "lyric" => MetaMessage::Lyric(args.text(arena)?),
+            "marker" => MetaMessage::Marker(args.text(arena)?),
+            "cue_point" => MetaMessage::CuePoint(args.text(arena)?),
+            "program_name" => MetaMessage::ProgramName(args.text(arena)?),
+            "device_name" => MetaMessage::DeviceName(args.text(arena)?),
+            "midi_channel" => MetaMessage::MidiChannel(args.u4()?),
+            "midi_port" => MetaMessage::MidiPort(args.u7()?),
+            "end_of_track" => MetaMessage::EndOfTrack,
+            "tempo" => MetaMessage::Tempo(
+                u24::try_from(args.int()?).ok_or(err_invalid!("integer out of range"))?,
+            ),
+            "smpte_offset" => {
+                let fps = Fps::from_int(args.int()?).ok_or(err_invalid!("invalid fps"))?;
+                let mut time = [0; 5];
+                for field in time.iter_mut() {
+                    *field = args.int()?;
+                }
+                let [hour, minute, second, frame, subframe] = time;
+                MetaMessage::SmpteOffset(
+                    SmpteTime::new(hour, minute, second, frame, subframe, fps)
+                        .ok_or(err_invalid!("invalid smpte offset"))?,
+                )
+            }
+            "time_signature" => {
+                MetaMessage::TimeSignature(args.int()?, args.int()?, args.int()?, args.int()?)
+            }
+            "key_signature" => MetaMessage::KeySignature(
+                args.int()?,
+                match args.word()? {
+                    "major" => false,
+                    "minor" => true,
+                    _ => bail!(err_invalid!("invalid key signature mode")),
+                },
+            ),
+            "sequencer_specific" => MetaMessage::SequencerSpecific(args.hex(arena)?),
+            "meta" => MetaMessage::Unknown(args.int()?, args.hex(arena)?),
+            _ => bail!(err_invalid!("unknown event name")),
+        }),
+    };
+    Ok(kind)
+}
+
+fn dump_kind(kind: &TrackEventKind, out: &mut impl fmt::Write) -> fmt::Result {
+    match *kind {
+        TrackEventKind::Midi { channel, message } => match message {
+            MidiMessage::NoteOff { key, vel } => {
+                write!(out, "note_off {} {} {}", channel, key, vel)
+            }
+            MidiMessage::NoteOn { key, vel } => write!(out, "note_on {} {} {}", channel, key, vel),
+            MidiMessage::Aftertouch { key, vel } => {
+                write!(out, "aftertouch {} {} {}", channel, key, vel)
+            }
+            MidiMessage::Controller { controller, value } => {
+                write!(out, "controller {} {} {}", channel, controller, value)
+            }
+            MidiMessage::ProgramChange { program } => {
+                write!(out, "program_change {} {}", channel, program)
+            }
+            MidiMessage::ChannelAftertouch { vel } => {
+                write!(out, "channel_aftertouch {} {}", channel, vel)
+            }
+            MidiMessage::PitchBend { bend } => write!(out, "pitch_bend {} {}", channel, bend.0),
+        },
+        TrackEventKind::SysEx(data) => dump_hex("sysex", data, out),
+        TrackEventKind::Escape(data) => dump_hex("escape", data, out),
+        TrackEventKind::Meta(meta) => match meta {
+            MetaMessage::TrackNumber(Some(num)) => write!(out, "track_number {}", num),
+            MetaMessage::TrackNumber(None) => write!(out, "track_number"),
+            MetaMessage::Text(text) => dump_text("text", text, out),
+            MetaMessage::Copyright(text) => dump_text("copyright", text, out),
+            MetaMessage::TrackName(text) => dump_text("track_name", text, out),
+            MetaMessage::InstrumentName(text) => dump_text("instrument_name", text, out),
+            MetaMessage::Lyric(text) => dump_text("lyric", text, out),
+            MetaMessage::Marker(text) => dump_text("marker", text, out),
+            MetaMessage::CuePoint(text) => dump_text("cue_point", text, out),
+            MetaMessage::ProgramName(text) => dump_text("program_name", text, out),
+            MetaMessage::DeviceName(text) => dump_text("device_name", text, out),
+            MetaMessage::MidiChannel(channel) => write!(out, "midi_channel {}", channel),
+            MetaMessage::MidiPort(port) => write!(out, "midi_port {}", port),
+            MetaMessage::EndOfTrack => write!(out, "end_of_track"),
+            MetaMessage::Tempo(tempo) => write!(out, "tempo {}", tempo),
+            MetaMessage::SmpteOffset(time) => write!(
+                out,
+                "smpte_offset {} {} {} {} {} {}",
+                time.fps().as_int(),
+                time.hour(),
+                time.minute(),
+                time.second(),
+                time.frame(),
+                time.subframe()
+            ),
+            MetaMessage::TimeSignature(num, denom, clocks, notes) => {
+                write!(out, "time_signature {} {} {} {}", num, denom, clocks, notes)
+            }
+            MetaMessage::KeySignature(sharps, minor) => write!(
+                out,
+                "key_signature {} {}",
+                sharps,
+                if minor { "minor" } else { "major" }
+            ),
+            MetaMessage::SequencerSpecific(data) => dump_hex("sequencer_specific", data, out),
+            MetaMessage::Unknown(ty, data) => {
+                write!(out, "meta {}", ty)?;
+                dump_hex("", data, out)
+            }
+        },
+    }
+}
+
+fn dump_hex(name: &str, data: &[u8], out: &mut impl fmt::Write) -> fmt::Result {
+    out.write_str(name)?;
+    for byte in data {
+        write!(out, " {:02X}", byte)?;
+    }
+    Ok(())
+}
+
+fn dump_text(name: &str, text: &[u8], out: &mut impl fmt::Write) -> fmt::Result {
+    write!(out, "{} \"", name)?;
+    for &byte in text {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{}", byte as char)?,
+            0x20..=0x7E => out.write_char(byte as char)?,
+            _ => write!(out, "\\x{:02X}", byte)?,
+        }
+    }
+    out.write_char('"')
+}
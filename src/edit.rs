@@ -0,0 +1,993 @@
+//! Utilities to modify tracks in-place.
+//!
+//! Tracks are plain lists of events with relative timing, which makes some common edits
+//! surprisingly fiddly to get right.
+//! The functions in this module take care of the details, such as keeping the timing of the
+//! untouched events intact.
+//!
+//! This module is only available with the `alloc` feature enabled.
+
+#![cfg(feature = "alloc")]
+
+use crate::{
+    event::{MetaMessage, MidiMessage, PitchBend, TrackEvent, TrackEventKind},
+    internal::*,
+    rpn::{Parameter, ParameterChange, RpnTracker},
+};
+use alloc::collections::BinaryHeap;
+use core::cmp::{Ordering, Reverse};
+
+/// Create a new empty track, consisting only of the obligatory `EndOfTrack` meta event.
+pub fn empty_track<'a>() -> Vec<TrackEvent<'a>> {
+    vec![TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    }]
+}
+
+/// Set the name of a track, replacing the current name if there is one.
+///
+/// If the track already has a `TrackName` meta event at tick 0 it is updated in place.
+/// Otherwise, a new `TrackName` event is inserted at the very start of the track.
+///
+/// The name is stored as UTF-8 bytes.
+pub fn set_track_name<'a>(track: &mut Vec<TrackEvent<'a>>, name: &'a str) {
+    let name = TrackEventKind::Meta(MetaMessage::TrackName(name.as_bytes()));
+    for ev in track.iter_mut() {
+        if ev.delta > 0 {
+            break;
+        }
+        if let TrackEventKind::Meta(MetaMessage::TrackName(_)) = ev.kind {
+            ev.kind = name;
+            return;
+        }
+    }
+    track.insert(
+        0,
+        TrackEvent {
+            delta: u28::new(0),
+            kind: name,
+        },
+    );
+}
+
+/// Get the name of a track, as set by a `TrackName` meta event at tick 0.
+///
+/// This is the name that [`set_track_name`](fn.set_track_name.html) sets or replaces.
+/// `TrackName` events later in the track are ignored.
+pub fn track_name<'a>(track: &[TrackEvent<'a>]) -> Option<&'a [u8]> {
+    track
+        .iter()
+        .take_while(|ev| ev.delta == 0)
+        .find_map(|ev| match ev.kind {
+            TrackEventKind::Meta(MetaMessage::TrackName(name)) => Some(name),
+            _ => None,
+        })
+}
+
+/// Get the total length of a track in ticks, that is, the sum of the delta times of all its
+/// events.
+pub fn duration_ticks(track: &[TrackEvent]) -> u64 {
+    absolute_ticks(track).last().unwrap_or(0)
+}
+
+/// Find out which channels are used by the MIDI events of a track.
+///
+/// Each entry of the returned array is `true` if there is at least one MIDI event on the
+/// corresponding channel.
+pub fn channels_used(track: &[TrackEvent]) -> [bool; 16] {
+    let mut used = [false; 16];
+    for ev in track {
+        if let TrackEventKind::Midi { channel, .. } = ev.kind {
+            used[channel.as_int() as usize] = true;
+        }
+    }
+    used
+}
+
+/// Transpose all notes in a track by the given amount of semitones.
+///
+/// The keys of `NoteOn`, `NoteOff` and `Aftertouch` messages are shifted, on all channels.
+/// Keys that would fall outside of the `0..=127` range are clamped.
+pub fn transpose(track: &mut [TrackEvent], semitones: i8) {
+    transpose_musical(track, semitones, 0);
+}
+
+/// Transpose all notes in a track by the given amount of semitones, except for the notes on the
+/// drum channels.
+///
+/// On a drum channel each key selects a different percussion instrument rather than a pitch,
+/// so transposing it would change the instruments instead.
+/// The drum channels are given as a bitmask where bit `n` stands for channel `n` (counting from
+/// 0), such as the one computed by [`Smf::drum_channels`](../struct.Smf.html#method.drum_channels).
+/// General MIDI reserves channel 10 for percussion, which is `1 << 9`.
+/// If `drum_channels` is `0` all channels are transposed, just like
+/// [`transpose`](fn.transpose.html).
+///
+/// Keys that would fall outside of the `0..=127` range are clamped.
+pub fn transpose_musical(track: &mut [TrackEvent], semitones: i8, drum_channels: u16) {
+    for ev in track.iter_mut() {
+        if let TrackEventKind::Midi {
+            channel,
+            message:
+                MidiMessage::NoteOn { key, .. }
+                | MidiMessage::NoteOff { key, .. }
+                | MidiMessage::Aftertouch { key, .. },
+        } = &mut ev.kind
+        {
+            if drum_channels & 1 << channel.as_int() == 0 {
+                *key = u7::new((key.as_int() as i16 + semitones as i16).clamp(0, 127) as u8);
+            }
+        }
+    }
+}
+
+/// Retain only the events for which the predicate returns `true`, similar to `Vec::retain`.
+///
+/// Unlike `Vec::retain`, the delta time of every removed event is folded into the next retained
+/// event, so the absolute timing of the remaining events is left untouched.
+/// Delta times that would overflow the 28-bit range are saturated.
+///
+/// If trailing events are removed their delta time is lost, since there is no event to carry it.
+pub fn retain<'a>(track: &mut Vec<TrackEvent<'a>>, mut keep: impl FnMut(&TrackEvent<'a>) -> bool) {
+    let mut carry = 0;
+    track.retain_mut(|ev| {
+        if keep(ev) {
+            ev.delta = saturating_delta(carry + ev.delta.as_int() as u64);
+            carry = 0;
+            true
+        } else {
+            carry += ev.delta.as_int() as u64;
+            false
+        }
+    });
+}
+
+/// Remove all meta events except for `EndOfTrack`, preserving the timing of the remaining events.
+///
+/// If `keep_tempo` is `true`, `Tempo` meta events are also kept, so that the playback speed of the
+/// track is preserved.
+pub fn strip_meta(track: &mut Vec<TrackEvent>, keep_tempo: bool) {
+    retain(track, |ev| match ev.kind {
+        TrackEventKind::Meta(MetaMessage::EndOfTrack) => true,
+        TrackEventKind::Meta(MetaMessage::Tempo(_)) => keep_tempo,
+        TrackEventKind::Meta(_) => false,
+        _ => true,
+    });
+}
+
+/// Make sure that the track ends with exactly one `EndOfTrack` meta event.
+///
+/// Any premature `EndOfTrack` events are removed, preserving the timing of the remaining events.
+/// If the track does not end with an `EndOfTrack` event, one is appended with a delta time of 0.
+///
+/// This is the invariant that the writer enforces by default when encoding a file (see
+/// [`WriteOptions`](../struct.WriteOptions.html)).
+pub fn ensure_end_of_track(track: &mut Vec<TrackEvent>) {
+    let last = track.len().saturating_sub(1);
+    let mut idx = 0;
+    retain(track, |ev| {
+        idx += 1;
+        idx - 1 == last || ev.kind != TrackEventKind::Meta(MetaMessage::EndOfTrack)
+    });
+    if track.last().map(|ev| ev.kind) != Some(TrackEventKind::Meta(MetaMessage::EndOfTrack)) {
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+    }
+}
+
+/// Remove all `EndOfTrack` meta events from a track, preserving the timing of the remaining
+/// events.
+///
+/// Returns the accumulated delta time of any trailing `EndOfTrack` events, that is, the time
+/// between the last remaining event and the end of the track.
+/// This is useful when concatenating tracks, since the returned time should be added to the delta
+/// time of the first event appended afterwards.
+/// Delta times that would overflow the 28-bit range are saturated.
+pub fn remove_end_of_track(track: &mut Vec<TrackEvent>) -> u28 {
+    let mut trailing = 0;
+    retain(track, |ev| {
+        if ev.kind == TrackEventKind::Meta(MetaMessage::EndOfTrack) {
+            trailing += ev.delta.as_int() as u64;
+            false
+        } else {
+            trailing = 0;
+            true
+        }
+    });
+    saturating_delta(trailing)
+}
+
+/// Extract the events of a single channel into a new track, preserving their timing.
+///
+/// Only the MIDI events on the given channel are kept, along with the global meta events that
+/// affect the interpretation of the whole file: `Tempo`, `TimeSignature`, `KeySignature`,
+/// `SmpteOffset` and `EndOfTrack`.
+/// SysEx events and any other meta events are dropped.
+pub fn extract_channel<'a>(track: &[TrackEvent<'a>], channel: u4) -> Vec<TrackEvent<'a>> {
+    let mut extracted = track.to_vec();
+    retain(&mut extracted, |ev| match ev.kind {
+        TrackEventKind::Midi { channel: ch, .. } => ch == channel,
+        TrackEventKind::Meta(meta) => matches!(
+            meta,
+            MetaMessage::Tempo(_)
+                | MetaMessage::TimeSignature(..)
+                | MetaMessage::KeySignature(..)
+                | MetaMessage::SmpteOffset(_)
+                | MetaMessage::EndOfTrack
+        ),
+        _ => false,
+    });
+    extracted
+}
+
+/// Apply a transfer curve to the pressure values of all aftertouch messages in a track.
+///
+/// The curve is applied to both `Aftertouch` (per-key pressure) and `ChannelAftertouch` messages,
+/// and receives the original pressure value, returning the new one.
+/// Since the curve returns a `u7`, the results are always within the 7-bit range.
+pub fn map_pressure(track: &mut [TrackEvent], mut curve: impl FnMut(u7) -> u7) {
+    for ev in track.iter_mut() {
+        match &mut ev.kind {
+            TrackEventKind::Midi {
+                message: MidiMessage::Aftertouch { vel, .. },
+                ..
+            }
+            | TrackEventKind::Midi {
+                message: MidiMessage::ChannelAftertouch { vel },
+                ..
+            } => *vel = curve(*vel),
+            _ => {}
+        }
+    }
+}
+
+/// Replace the velocity of every `NoteOn` message in a track through a lookup table, which maps
+/// each original velocity to its new value.
+///
+/// This allows arbitrary dynamics shaping, such as compression or expansion, in a single pass.
+/// Entries of the table are clamped to the 7-bit range.
+/// `NoteOn` messages with a velocity of zero are releases, so they are left untouched, and
+/// entries of zero are raised to 1 so that notes are never turned into releases.
+pub fn apply_velocity_curve(track: &mut [TrackEvent], lut: &[u8; 128]) {
+    for ev in track.iter_mut() {
+        if let TrackEventKind::Midi {
+            message: MidiMessage::NoteOn { vel, .. },
+            ..
+        } = &mut ev.kind
+        {
+            if *vel > 0 {
+                *vel = u7::new(lut[vel.as_int() as usize].clamp(1, 127));
+            }
+        }
+    }
+}
+
+/// Remove `Controller` messages that set a controller to the value it already holds, preserving
+/// the timing of the remaining events.
+///
+/// Recordings of continuous controllers, such as expression pedals, often contain long runs of
+/// identical values, which this thins out without changing the sound.
+/// The last value of each controller is tracked per channel, and is forgotten on a "reset all
+/// controllers" message (controller 121).
+/// Data entry controllers (6, 38, 96 and 97) and channel mode messages (controllers 120 to 127)
+/// act every time they are sent, so they are never removed.
+pub fn dedup_controllers(track: &mut Vec<TrackEvent>) {
+    let mut values = [[None; 120]; 16];
+    retain(track, |ev| match ev.kind {
+        TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::Controller { controller, value },
+        } => {
+            let values = &mut values[channel.as_int() as usize];
+            match controller.as_int() {
+                6 | 38 | 96 | 97 => true,
+                121 => {
+                    *values = [None; 120];
+                    true
+                }
+                ctrl @ 0..=119 => values[ctrl as usize].replace(value) != Some(value),
+                _ => true,
+            }
+        }
+        _ => true,
+    });
+}
+
+/// Remove `PitchBend` messages whose value differs by less than `tolerance` from the last bend
+/// kept on the same channel, preserving the timing of the remaining events.
+///
+/// Bends recorded from a wheel are a dense stream of tiny steps, most of which can be dropped
+/// without an audible difference.
+/// A `tolerance` of 1 only removes repeated values, and a `tolerance` of 0 removes nothing.
+/// So that the pitch does not settle slightly off, the last bend before each note release on the
+/// same channel and the last bend of each channel in the track are always kept.
+/// The first bend after a "reset all controllers" message (controller 121) is always kept too.
+pub fn thin_pitch_bend(track: &mut Vec<TrackEvent>, tolerance: u16) {
+    // The last kept bend value of each channel, and the latest removed bend since then
+    let mut last = [None; 16];
+    let mut pending = [None; 16];
+    let mut keep = vec![true; track.len()];
+    for (i, ev) in track.iter().enumerate() {
+        if let TrackEventKind::Midi { channel, message } = ev.kind {
+            let ch = channel.as_int() as usize;
+            match message {
+                MidiMessage::PitchBend { bend } => {
+                    let value = bend.0.as_int();
+                    match last[ch] {
+                        Some(prev) if value.max(prev) - value.min(prev) < tolerance => {
+                            keep[i] = false;
+                            pending[ch] = Some((i, value));
+                        }
+                        _ => {
+                            last[ch] = Some(value);
+                            pending[ch] = None;
+                        }
+                    }
+                }
+                MidiMessage::NoteOn { vel, .. } if vel > 0 => {}
+                MidiMessage::NoteOn { .. } | MidiMessage::NoteOff { .. } => {
+                    if let Some((idx, value)) = pending[ch].take() {
+                        keep[idx] = true;
+                        last[ch] = Some(value);
+                    }
+                }
+                MidiMessage::Controller { controller, .. } if controller == 121 => {
+                    last[ch] = None;
+                    pending[ch] = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    for (idx, _) in pending.iter().flatten() {
+        keep[*idx] = true;
+    }
+    let mut keep = keep.into_iter();
+    retain(track, |_| keep.next().unwrap_or(true));
+}
+
+/// Make a channel monophonic, so that at most one note is sounding on it at any time.
+///
+/// Whenever a note starts while another note is held on the same channel, the held note is
+/// released right before the new one starts (last-note priority).
+/// The original release of a note that was cut short is removed, preserving the timing of the
+/// remaining events.
+/// A note still held at the end of the track is released right before the `EndOfTrack` event, or
+/// at the very end of the track if there is no such event.
+///
+/// The inserted `NoteOff` events have the default release velocity of 64.
+/// Events on other channels are left untouched.
+pub fn make_monophonic(track: &mut Vec<TrackEvent>, channel: u4) {
+    fn push<'a>(mono: &mut Vec<TrackEvent<'a>>, delta: &mut u64, kind: TrackEventKind<'a>) {
+        mono.push(TrackEvent {
+            delta: saturating_delta(mem::take(delta)),
+            kind,
+        });
+    }
+    let note_off = |key| TrackEventKind::Midi {
+        channel,
+        message: MidiMessage::NoteOff {
+            key,
+            vel: u7::new(64),
+        },
+    };
+    let mut held = None;
+    // How many releases of each key to drop, because their note was already cut short
+    let mut stale = [0u32; 128];
+    let mut delta = 0;
+    let mut mono = Vec::with_capacity(track.len());
+    for ev in track.drain(..) {
+        delta += ev.delta.as_int() as u64;
+        match ev.kind {
+            TrackEventKind::Midi {
+                channel: ch,
+                message,
+            } if ch == channel => match message {
+                MidiMessage::NoteOn { key, vel } if vel > 0 => {
+                    if let Some(prev) = held.replace(key) {
+                        stale[prev.as_int() as usize] += 1;
+                        push(&mut mono, &mut delta, note_off(prev));
+                    }
+                    push(&mut mono, &mut delta, ev.kind);
+                }
+                MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                    let stale = &mut stale[key.as_int() as usize];
+                    if *stale > 0 {
+                        // Fold the delta time into the next event
+                        *stale -= 1;
+                        continue;
+                    }
+                    if held == Some(key) {
+                        held = None;
+                    }
+                    push(&mut mono, &mut delta, ev.kind);
+                }
+                _ => push(&mut mono, &mut delta, ev.kind),
+            },
+            TrackEventKind::Meta(MetaMessage::EndOfTrack) => {
+                if let Some(prev) = held.take() {
+                    push(&mut mono, &mut delta, note_off(prev));
+                }
+                push(&mut mono, &mut delta, ev.kind);
+            }
+            _ => push(&mut mono, &mut delta, ev.kind),
+        }
+    }
+    if let Some(prev) = held {
+        push(&mut mono, &mut delta, note_off(prev));
+    }
+    *track = mono;
+}
+
+/// Randomly perturb the timing and velocity of the notes in a track, to make sequenced music feel
+/// less mechanical.
+///
+/// Each note is moved by a random offset of up to `timing_jitter_ticks` ticks in either
+/// direction, moving its `NoteOn` and the matching `NoteOff` together so that the note length is
+/// kept.
+/// Notes are never moved before the start or past the end of the track, although notes that
+/// would be released past the end of the track are shortened.
+/// Overlapping notes of the same key are paired with their releases first-in first-out, just like
+/// [`iter::Notes`](../iter/struct.Notes.html) does.
+/// The velocity of each `NoteOn` is changed by up to `velocity_jitter` in either direction,
+/// clamped to the `1..=127` range so that notes are never silenced.
+/// All other events are left at their original position.
+///
+/// The random numbers come from a simple deterministic generator initialized with `seed`, so the
+/// same seed always produces the same result.
+///
+/// If any resulting delta time does not fit in 28 bits an error is returned and the track is left
+/// unmodified.
+pub fn humanize(
+    track: &mut Vec<TrackEvent>,
+    timing_jitter_ticks: u32,
+    velocity_jitter: u8,
+    seed: u64,
+) -> Result<()> {
+    let mut rng = SplitMix64(seed);
+    let mut jitter = |max: u32| {
+        let span = 2 * max as u64 + 1;
+        (rng.next() % span) as i64 - max as i64
+    };
+    let end = absolute_ticks(track).last().unwrap_or(0);
+    // Offsets of the notes that are currently playing, as `(channel, key, offset)`
+    let mut playing = Vec::new();
+    let mut events = track
+        .iter()
+        .zip(absolute_ticks(track))
+        .map(|(ev, tick)| {
+            let mut kind = ev.kind;
+            let mut offset = 0;
+            if let TrackEventKind::Midi { channel, message } = &mut kind {
+                match message {
+                    MidiMessage::NoteOn { key, vel } if *vel > 0 => {
+                        offset =
+                            jitter(timing_jitter_ticks).clamp(-(tick as i64), (end - tick) as i64);
+                        let new_vel = vel.as_int() as i64 + jitter(velocity_jitter as u32);
+                        *vel = u7::new(new_vel.clamp(1, 127) as u8);
+                        playing.push((*channel, *key, offset));
+                    }
+                    MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                        if let Some(idx) = playing
+                            .iter()
+                            .position(|&(ch, k, _)| ch == *channel && k == *key)
+                        {
+                            offset = playing.remove(idx).2;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let tick = (tick as i64 + offset).clamp(0, end as i64) as u64;
+            (tick, kind)
+        })
+        .collect::<Vec<_>>();
+    // `sort_by_key` is stable, so events that were not moved keep their relative order
+    events.sort_by_key(|&(tick, _)| tick);
+    let ticks = events.iter().map(|&(tick, _)| tick).collect::<Vec<_>>();
+    let mut humanized = events
+        .into_iter()
+        .map(|(_, kind)| TrackEvent {
+            delta: u28::new(0),
+            kind,
+        })
+        .collect::<Vec<_>>();
+    set_absolute_ticks(&mut humanized, &ticks)?;
+    *track = humanized;
+    Ok(())
+}
+
+/// Snap the length of every note in a track to a multiple of `grid` ticks, without moving the
+/// start of any note.
+///
+/// Each `NoteOff` (or `NoteOn` with zero velocity) is moved so that the distance from its
+/// matching `NoteOn` is rounded to the nearest multiple of `grid` (rounding halves up), and is at
+/// least `grid` ticks long.
+/// Overlapping notes of the same key are paired with their releases first-in first-out, just like
+/// [`iter::Notes`](../iter/struct.Notes.html) does.
+/// For example, with a resolution of 480 ticks per beat, a grid of 120 ticks snaps lengths to
+/// sixteenth notes.
+/// Releases are never moved past the next start of the same key on the same channel, nor past
+/// the end of the track, so notes may end up shorter than the grid requires.
+/// All other events are left at their original position.
+///
+/// A grid of zero leaves the track untouched.
+/// If any resulting delta time does not fit in 28 bits an error is returned and the track is left
+/// unmodified.
+pub fn quantize_durations(track: &mut Vec<TrackEvent>, grid: u32) -> Result<()> {
+    if grid == 0 {
+        return Ok(());
+    }
+    let grid = grid as u64;
+    let ticks = absolute_ticks(track).collect::<Vec<_>>();
+    let end = ticks.last().copied().unwrap_or(0);
+    let note_on = |kind: &TrackEventKind| match *kind {
+        TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::NoteOn { key, vel },
+        } if vel > 0 => Some((channel, key)),
+        _ => None,
+    };
+    // For each event, the tick of the next note start with each channel and key, if any
+    let mut next_start = vec![None; 16 * 128];
+    let mut limits = vec![end; track.len()];
+    for (idx, ev) in track.iter().enumerate().rev() {
+        if let TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. },
+        } = ev.kind
+        {
+            let slot = &mut next_start[channel.as_int() as usize * 128 + key.as_int() as usize];
+            limits[idx] = slot.unwrap_or(end);
+            if note_on(&ev.kind).is_some() {
+                *slot = Some(ticks[idx]);
+            }
+        }
+    }
+    // Start ticks of the notes that are currently playing, as `(channel, key, start)`
+    let mut playing = Vec::new();
+    let mut events = track
+        .iter()
+        .zip(ticks.iter().copied())
+        .enumerate()
+        .map(|(idx, (ev, tick))| {
+            if let Some((channel, key)) = note_on(&ev.kind) {
+                playing.push((channel, key, tick));
+            } else if let TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. },
+            } = ev.kind
+            {
+                if let Some(pos) = playing
+                    .iter()
+                    .position(|&(ch, k, _)| ch == channel && k == key)
+                {
+                    let start = playing.remove(pos).2;
+                    let len = ((tick - start + grid / 2) / grid).max(1) * grid;
+                    return ((start + len).min(limits[idx]), ev.kind);
+                }
+            }
+            (tick, ev.kind)
+        })
+        .collect::<Vec<_>>();
+    // `sort_by_key` is stable, so events that were not moved keep their relative order
+    events.sort_by_key(|&(tick, _)| tick);
+    let ticks = events.iter().map(|&(tick, _)| tick).collect::<Vec<_>>();
+    let mut quantized = events
+        .into_iter()
+        .map(|(_, kind)| TrackEvent {
+            delta: u28::new(0),
+            kind,
+        })
+        .collect::<Vec<_>>();
+    set_absolute_ticks(&mut quantized, &ticks)?;
+    *track = quantized;
+    Ok(())
+}
+
+/// A tiny deterministic pseudo-random number generator, good enough for musical purposes.
+struct SplitMix64(u64);
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Scale the tempo of a track by the given factor, without touching the timing of any event.
+///
+/// The microseconds-per-beat of every `Tempo` meta event are multiplied by `factor`, rounding to
+/// the nearest microsecond and clamping to the `1 ..= 0xFFFFFF` range.
+/// Note that a `Tempo` event specifies the length of a beat, so a factor greater than 1 slows down
+/// playback, and a factor of 1.25 plays the track at 0.8x speed.
+///
+/// If the track has no `Tempo` event at all, the default tempo of 120 beats per minute is scaled
+/// and inserted at the start of the track.
+pub fn scale_tempo(track: &mut Vec<TrackEvent>, factor: f64) {
+    let scale = |tempo: u24| {
+        let scaled = (tempo.as_int() as f64 * factor + 0.5) as u32;
+        u24::new(scaled.clamp(1, u24::max_value().as_int()))
+    };
+    let mut found = false;
+    for ev in track.iter_mut() {
+        if let TrackEventKind::Meta(MetaMessage::Tempo(tempo)) = &mut ev.kind {
+            *tempo = scale(*tempo);
+            found = true;
+        }
+    }
+    if !found {
+        track.insert(
+            0,
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::Tempo(scale(u24::new(500_000)))),
+            },
+        );
+    }
+}
+
+/// Shift all events of a track earlier by the given amount of ticks.
+///
+/// Events that would land before the start of the track are moved to tick 0, keeping their
+/// relative order.
+pub fn trim_start(track: &mut [TrackEvent], ticks: u64) {
+    let mut remaining = ticks;
+    for ev in track.iter_mut() {
+        if remaining == 0 {
+            break;
+        }
+        let delta = ev.delta.as_int() as u64;
+        let trimmed = delta.min(remaining);
+        ev.delta = u28::new((delta - trimmed) as u32);
+        remaining -= trimmed;
+    }
+}
+
+/// Resolve the state of every channel right before the given absolute tick into a preamble of
+/// events, so that an excerpt of the track starting at that tick sounds the same as in the
+/// original.
+///
+/// The preamble contains, for each channel that was set up before `tick`, the latest bank select
+/// controllers, program change, other controller values and pitch bend, in that order.
+/// Events at exactly `tick` are considered part of the excerpt, and are not resolved.
+/// The preamble is meant to be placed at the start of the excerpt with a delta time of 0.
+///
+/// Channel mode messages (controllers 120 to 127) are not part of the state, although a "reset
+/// all controllers" message forgets the controller values, pitch bend and parameter selection
+/// seen before it.
+///
+/// The values of registered and non-registered parameters (RPN and NRPN) are tracked separately
+/// for each parameter, as decoded by [`RpnTracker`](../struct.RpnTracker.html).
+/// Each parameter that was given a value is restored by selecting it and setting its value
+/// through the data entry controllers, in the order in which the parameters were first set.
+/// The parameter selection in effect at `tick` is restored last, without any data entry, so that
+/// later data entry in the excerpt applies to the same parameter as in the original.
+pub fn freeze<'a>(track: &[TrackEvent<'a>], tick: u64) -> Vec<TrackEventKind<'a>> {
+    // Controllers that must be written in a particular order, or not at all
+    const BANK: [u8; 2] = [0, 32];
+    const NRPN: [u8; 2] = [99, 98];
+    const RPN: [u8; 2] = [101, 100];
+    const DATA: [u8; 4] = [6, 38, 96, 97];
+    #[derive(Clone)]
+    struct State {
+        program: Option<u7>,
+        controllers: [Option<u7>; 120],
+        bend: Option<PitchBend>,
+        /// The parameters that were given a value, in the order they were first set
+        parameters: Vec<(Parameter, u14)>,
+        /// Whether the selection controllers hold a registered parameter
+        registered: bool,
+    }
+    let mut channels = vec![
+        State {
+            program: None,
+            controllers: [None; 120],
+            bend: None,
+            parameters: Vec::new(),
+            registered: true,
+        };
+        16
+    ];
+    let mut rpn = RpnTracker::new();
+    for (at, kind) in absolute_events(track) {
+        if at >= tick {
+            break;
+        }
+        if let TrackEventKind::Midi { channel, message } = kind {
+            let state = &mut channels[channel.as_int() as usize];
+            if let Some(ParameterChange {
+                parameter, value, ..
+            }) = rpn.feed(channel, &message)
+            {
+                match state.parameters.iter_mut().find(|(p, _)| *p == parameter) {
+                    Some((_, old)) => *old = value,
+                    None => state.parameters.push((parameter, value)),
+                }
+            }
+            match message {
+                MidiMessage::ProgramChange { program } => state.program = Some(program),
+                MidiMessage::PitchBend { bend } => state.bend = Some(bend),
+                MidiMessage::Controller { controller, value } => match controller.as_int() {
+                    121 => {
+                        state.controllers = [None; 120];
+                        state.bend = None;
+                        // Deselect the parameter, just like the receiving device does
+                        for &ctrl in RPN.iter() {
+                            rpn.feed(
+                                channel,
+                                &MidiMessage::Controller {
+                                    controller: u7::new(ctrl),
+                                    value: u7::new(0x7F),
+                                },
+                            );
+                        }
+                    }
+                    ctrl @ 0..=119 => {
+                        // Selecting the other kind of parameter replaces the whole selection
+                        let registered = RPN.contains(&ctrl);
+                        if (registered || NRPN.contains(&ctrl)) && registered != state.registered {
+                            for ctrl in RPN.iter().chain(NRPN.iter()) {
+                                state.controllers[*ctrl as usize] = None;
+                            }
+                            state.registered = registered;
+                        }
+                        state.controllers[ctrl as usize] = Some(value);
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+    let ordered = [&BANK[..], &NRPN, &RPN, &DATA];
+    let rest = (0..120)
+        .filter(|ctrl| !ordered.iter().any(|group| group.contains(ctrl)))
+        .collect::<Vec<_>>();
+    let mut preamble = Vec::new();
+    for (channel, state) in channels.iter().enumerate() {
+        let channel = u4::new(channel as u8);
+        let cc = |ctrl: u8, value: u7| TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::Controller {
+                controller: u7::new(ctrl),
+                value,
+            },
+        };
+        let controllers = |ctrls: &[u8], preamble: &mut Vec<_>| {
+            for &ctrl in ctrls {
+                if let Some(value) = state.controllers[ctrl as usize] {
+                    preamble.push(cc(ctrl, value));
+                }
+            }
+        };
+        controllers(&BANK, &mut preamble);
+        if let Some(program) = state.program {
+            preamble.push(TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::ProgramChange { program },
+            });
+        }
+        controllers(&rest, &mut preamble);
+        for &(parameter, value) in state.parameters.iter() {
+            let (select, number) = match parameter {
+                Parameter::Registered(number) => (RPN, number),
+                Parameter::NonRegistered(number) => (NRPN, number),
+            };
+            let (number, value) = (number.as_int(), value.as_int());
+            preamble.push(cc(select[0], u7::new((number >> 7) as u8)));
+            preamble.push(cc(select[1], u7::new(number as u8 & 0x7F)));
+            preamble.push(cc(DATA[0], u7::new((value >> 7) as u8)));
+            // A data entry MSB resets the LSB to zero
+            if value & 0x7F != 0 {
+                preamble.push(cc(DATA[1], u7::new(value as u8 & 0x7F)));
+            }
+        }
+        controllers(if state.registered { &RPN } else { &NRPN }, &mut preamble);
+        if let Some(bend) = state.bend {
+            preamble.push(TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::PitchBend { bend },
+            });
+        }
+    }
+    preamble
+}
+
+/// Merge several tracks into a single track, interleaving their events by time.
+///
+/// Events that happen at the same tick are kept in their original order: first ordered by the
+/// track they come from, and then by their order within that track.
+/// This matters for events such as a `ProgramChange` followed by a `NoteOn` at the same tick.
+///
+/// `EndOfTrack` events are removed from the input tracks, and a single `EndOfTrack` event is
+/// placed at the end of the merged track, when the longest of the tracks ends.
+/// Delta times that would overflow the 28-bit range are saturated.
+///
+/// This is the main step when converting a `Parallel` file into a `SingleTrack` file.
+pub fn merge<'a, 'b, T>(tracks: T) -> Vec<TrackEvent<'a>>
+where
+    'a: 'b,
+    T: IntoIterator,
+    T::Item: IntoIterator<Item = &'b TrackEvent<'a>>,
+{
+    let mut tracks = tracks
+        .into_iter()
+        .map(|track| (track.into_iter(), 0))
+        .collect::<Vec<_>>();
+    let count = tracks.len();
+    let mut end = 0;
+    let mut heap = BinaryHeap::with_capacity(count);
+    // Only one event per track is in the heap at any time, so using the track index as the
+    // sequence number keeps both the track order and the order within each track
+    let mut advance = |heap: &mut BinaryHeap<_>, seq: usize| {
+        let (track, tick) = &mut tracks[seq];
+        for ev in track {
+            *tick += ev.delta.as_int() as u64;
+            if ev.kind != TrackEventKind::Meta(MetaMessage::EndOfTrack) {
+                heap.push(Reverse(TimedEvent {
+                    tick: *tick,
+                    seq,
+                    event: ev.kind,
+                }));
+                return;
+            }
+        }
+        end = end.max(*tick);
+    };
+    for seq in 0..count {
+        advance(&mut heap, seq);
+    }
+    let mut last = 0;
+    let mut merged = Vec::new();
+    while let Some(Reverse(ev)) = heap.pop() {
+        merged.push(TrackEvent {
+            delta: saturating_delta(ev.tick - mem::replace(&mut last, ev.tick)),
+            kind: ev.event,
+        });
+        advance(&mut heap, ev.seq);
+    }
+    merged.push(TrackEvent {
+        delta: saturating_delta(end - last),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+    merged
+}
+
+/// An event timestamped with an absolute tick, as used when merging several tracks.
+///
+/// Timed events are ordered by `tick`, and events at the same tick are ordered by `seq`, a
+/// sequence number chosen by the caller to break ties in a stable way (for example, the index of
+/// the source track).
+/// The `event` itself does not take part in comparisons, so two timed events with the same `tick`
+/// and `seq` compare as equal even if their events differ.
+///
+/// Since `BinaryHeap` is a max-heap, wrap timed events in `core::cmp::Reverse` to pop the earliest
+/// event first when doing a k-way merge.
+#[derive(Copy, Clone, Debug)]
+pub struct TimedEvent<'a> {
+    /// The absolute tick at which the event happens.
+    pub tick: u64,
+    /// A sequence number used to order simultaneous events.
+    pub seq: usize,
+    /// The event itself.
+    pub event: TrackEventKind<'a>,
+}
+impl PartialEq for TimedEvent<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for TimedEvent<'_> {}
+impl PartialOrd for TimedEvent<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for TimedEvent<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.tick, self.seq).cmp(&(other.tick, other.seq))
+    }
+}
+
+/// Build a track out of events timestamped with absolute ticks, which may be out of order.
+///
+/// The events are stably sorted by tick, so events at the same tick keep their relative order,
+/// and then converted into delta times.
+/// This is the canonical way to build a track out of events generated in arbitrary order.
+///
+/// If two consecutive events are too far apart for their delta time to fit in 28 bits, an error
+/// is returned.
+pub fn sort_track<'a>(
+    events: impl IntoIterator<Item = (u64, TrackEventKind<'a>)>,
+) -> Result<Vec<TrackEvent<'a>>> {
+    let mut events = events.into_iter().collect::<Vec<_>>();
+    events.sort_by_key(|&(tick, _)| tick);
+    let ticks = events.iter().map(|&(tick, _)| tick).collect::<Vec<_>>();
+    let mut track = events
+        .into_iter()
+        .map(|(_, kind)| TrackEvent {
+            delta: u28::new(0),
+            kind,
+        })
+        .collect::<Vec<_>>();
+    set_absolute_ticks(&mut track, &ticks)?;
+    Ok(track)
+}
+
+/// Iterate over the events of a track, each one along with its absolute tick.
+///
+/// This is the inverse of [`sort_track`](fn.sort_track.html): the events can be edited in
+/// absolute time, and then turned back into a track.
+pub fn absolute_events<'a, 'b>(
+    track: &'b [TrackEvent<'a>],
+) -> impl Iterator<Item = (u64, TrackEventKind<'a>)> + 'b {
+    absolute_ticks(track).zip(track.iter().map(|ev| ev.kind))
+}
+
+/// Convert an arbitrary tick count into a delta time, saturating if it does not fit in 28 bits.
+fn saturating_delta(ticks: u64) -> u28 {
+    u32::try_from(ticks)
+        .ok()
+        .and_then(u28::try_from)
+        .unwrap_or_else(u28::max_value)
+}
+
+/// Rescale the timing of a track from one ticks-per-beat resolution to another.
+///
+/// Each event is placed at its absolute tick multiplied by `to_tpq / from_tpq`, rounding to the
+/// nearest tick (rounding halves up).
+/// Because rounding is done on absolute ticks rather than on each delta, rounding errors do not
+/// accumulate along the track.
+///
+/// If any resulting delta time does not fit in 28 bits an error is returned and the track is left
+/// unmodified.
+///
+/// # Panics
+///
+/// Panics if `from_tpq` is zero.
+pub fn rescale_resolution(track: &mut Vec<TrackEvent>, from_tpq: u16, to_tpq: u16) -> Result<()> {
+    assert!(from_tpq != 0, "cannot rescale from a resolution of 0");
+    let (from, to) = (from_tpq as u128, to_tpq as u128);
+    let ticks = absolute_ticks(track)
+        .map(|tick| ((tick as u128 * to + from / 2) / from) as u64)
+        .collect::<Vec<_>>();
+    set_absolute_ticks(track, &ticks)
+}
+
+/// Iterate over the absolute tick of each event in a track.
+fn absolute_ticks<'a>(track: &'a [TrackEvent]) -> impl Iterator<Item = u64> + 'a {
+    track.iter().scan(0, |tick, ev| {
+        *tick += ev.delta.as_int() as u64;
+        Some(*tick)
+    })
+}
+
+/// Rewrite the delta times of a track so that each event lands on the given absolute tick.
+///
+/// The ticks must be sorted.
+/// If any delta time does not fit in 28 bits an error is returned and the track is left
+/// unmodified.
+fn set_absolute_ticks(track: &mut [TrackEvent], ticks: &[u64]) -> Result<()> {
+    debug_assert_eq!(track.len(), ticks.len());
+    let mut last = 0;
+    let deltas = ticks
+        .iter()
+        .map(|&tick| {
+            let delta = u32::try_from(tick - mem::replace(&mut last, tick))
+                .ok()
+                .and_then(u28::try_from)
+                .ok_or(err_invalid!("delta time exceeds 28 bits"))?;
+            Ok(delta)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (ev, delta) in track.iter_mut().zip(deltas) {
+        ev.delta = delta;
+    }
+    Ok(())
+}
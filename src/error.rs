@@ -79,7 +79,8 @@ mod error_impl {
 /// In release mode it is a newtype wrapper around `ErrorKind`, so the `Error::source` method
 /// always returns `None`.
 ///
-/// If the `std` feature is enabled, this type implements `std::error::Error`.
+/// If the `std` feature is enabled, this type implements `std::error::Error` (including the
+/// `source` chain of context errors) and can be converted into a `std::io::Error`.
 /// Otherwise, only `Display` and `Debug` are implemented (the `source` method on the `Error` type
 /// itself is still available, though).
 ///
@@ -140,6 +141,16 @@ impl std::error::Error for Error {
     }
 }
 
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    /// Wrap a MIDI error into an `InvalidData` IO error, so that parsing errors can be propagated
+    /// from functions that return `std::io::Result`.
+    #[inline]
+    fn from(err: Error) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
 trait ErrorExt {
     fn kind(&self) -> ErrorKind;
     fn source(&self) -> Option<&Error>;
@@ -190,6 +201,9 @@ impl fmt::Display for ErrorKind {
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for ErrorKind {}
+
 macro_rules! err_invalid {
     ($msg:expr) => {{
         const ERR_KIND: &'static ErrorKind = &ErrorKind::Invalid($msg);
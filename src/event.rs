@@ -1,10 +1,12 @@
 //! All sort of events and their parsers.
 
 use crate::{
+    internal::*,
     live::{LiveEvent, SystemCommon},
-    prelude::*,
     primitive::{read_varlen_slice, write_varlen_slice, SmpteTime},
 };
+#[cfg(feature = "alloc")]
+use alloc::borrow::Cow;
 
 /// Represents a parsed SMF track event.
 ///
@@ -18,6 +20,23 @@ pub struct TrackEvent<'a> {
     pub kind: TrackEventKind<'a>,
 }
 impl<'a> TrackEvent<'a> {
+    /// Create a new track event out of a plain integer delta time.
+    ///
+    /// Returns `None` if the delta time does not fit in 28 bits.
+    #[inline]
+    pub fn try_new(delta: u32, kind: TrackEventKind<'a>) -> Option<TrackEvent<'a>> {
+        Some(TrackEvent {
+            delta: u28::try_from(delta)?,
+            kind,
+        })
+    }
+
+    /// Get the delta time of this event as a plain integer, in MIDI ticks.
+    #[inline]
+    pub fn delta_ticks(&self) -> u32 {
+        self.delta.as_int()
+    }
+
     /// Advances the slice and updates `running_status`.
     ///
     /// In case of failure the slice might be left in the middle of an event!
@@ -67,6 +86,96 @@ impl<'a> TrackEvent<'a> {
     }
 }
 
+impl<'a> From<TrackEventKind<'a>> for TrackEvent<'a> {
+    /// Wrap an event kind into a track event with a delta time of zero.
+    fn from(kind: TrackEventKind<'a>) -> Self {
+        TrackEvent {
+            delta: u28::new(0),
+            kind,
+        }
+    }
+}
+
+/// A track event decoded only to the level of its status byte and data bytes, without
+/// interpreting them.
+///
+/// Yielded by [`RawEventIter`](struct.RawEventIter.html), which only parses as much as needed to
+/// find the boundaries of each event.
+/// This makes it faster than full parsing, and suitable for tools that show or re-emit events
+/// verbatim, such as hex editors.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct RawEvent<'a> {
+    /// How many MIDI ticks after the previous event should this event fire.
+    pub delta: u28,
+    /// The status byte of the event.
+    ///
+    /// If the event uses running status, this is the running status in effect, so it is always
+    /// a full status byte with its top bit set.
+    /// Whether the status byte actually appears in the track is given by `running`.
+    pub status: u8,
+    /// Whether the status byte was omitted from the track, relying on running status instead.
+    ///
+    /// Together with the other fields, this allows re-emitting the event exactly as it appears in
+    /// the track.
+    pub running: bool,
+    /// The bytes that follow the status byte, exactly as they appear in the track.
+    ///
+    /// For MIDI messages these are the data bytes of the message.
+    /// For SysEx events and escapes, this is the variable-length data length followed by the data.
+    /// For meta events, this is the meta type byte, followed by the variable-length data length
+    /// and the data.
+    /// The data bytes of MIDI messages are checked to be 7-bit, just like when fully parsing them.
+    pub data: &'a [u8],
+}
+impl<'a> RawEvent<'a> {
+    /// Advances the slice and updates `running_status`.
+    ///
+    /// In case of failure the slice might be left in the middle of an event!
+    pub(crate) fn read(
+        raw: &mut &'a [u8],
+        running_status: &mut Option<u8>,
+    ) -> Result<RawEvent<'a>> {
+        let delta = u28::read_u7(raw).context(err_invalid!("failed to read event deltatime"))?;
+        let mut status = *raw.first().ok_or(err_invalid!("failed to read status"))?;
+        let running = status < 0x80;
+        if running {
+            status = running_status.ok_or(err_invalid!(
+                "event missing status with no running status active"
+            ))?;
+        } else {
+            *raw = &raw[1..];
+        }
+        let start = *raw;
+        match status {
+            0x80..=0xEF => {
+                *running_status = Some(status);
+                let data = raw
+                    .split_checked(MidiMessage::msg_length(status))
+                    .ok_or(err_invalid!("truncated midi message"))?;
+                for &byte in data {
+                    u7::check_int(byte)?;
+                }
+            }
+            0xF0 | 0xF7 | 0xFF => {
+                *running_status = None;
+                if status == 0xFF {
+                    u8::read(raw).context(err_invalid!("failed to read meta event"))?;
+                }
+                read_varlen_slice(raw).context(err_invalid!("failed to read event data"))?;
+            }
+            _ => bail!(err_invalid!(
+                "standard midi files cannot contain system messages"
+            )),
+        }
+        Ok(RawEvent {
+            delta,
+            status,
+            running,
+            data: &start[..start.len() - raw.len()],
+        })
+    }
+}
+
 /// Represents the different kinds of SMF events and their associated data.
 ///
 /// It notably does *not* include the timing of the event; the `TrackEvent` struct is responsible
@@ -98,7 +207,7 @@ pub enum TrackEventKind<'a> {
 impl<'a> TrackEventKind<'a> {
     fn read(raw: &mut &'a [u8], running_status: &mut Option<u8>) -> Result<TrackEventKind<'a>> {
         //Read status
-        let mut status = *raw.get(0).ok_or(err_invalid!("failed to read status"))?;
+        let mut status = *raw.first().ok_or(err_invalid!("failed to read status"))?;
         if status < 0x80 {
             //Running status!
             status = running_status.ok_or(err_invalid!(
@@ -110,6 +219,9 @@ impl<'a> TrackEventKind<'a> {
             *raw = &raw[1..];
         }
         //Delegate further parsing depending on status
+        //Only MIDI messages become the running status, any other message cancels it, so that a
+        //following event without a status byte is rejected instead of reusing `0xF0`, `0xF7` or
+        //`0xFF` as its status
         let kind = match status {
             0x80..=0xEF => {
                 *running_status = Some(status);
@@ -158,8 +270,8 @@ impl<'a> TrackEventKind<'a> {
         // - Escape (0xF7) cancels and cannot use running status
         // - Meta Messages (0xFF) cancel and cannot use running status
         match self {
-            TrackEventKind::Midi { channel, message } => {
-                let status = message.status_nibble() << 4 | channel.as_int();
+            TrackEventKind::Midi { message, .. } => {
+                let status = self.status_byte();
                 if Some(status) != *running_status {
                     //Explicitly write status
                     out.write(&[status])?;
@@ -186,6 +298,61 @@ impl<'a> TrackEventKind<'a> {
         Ok(())
     }
 
+    /// Create a MIDI event on the given channel.
+    ///
+    /// Shorthand for `TrackEventKind::Midi { channel, message }`.
+    #[inline]
+    pub fn midi(channel: u4, message: MidiMessage) -> TrackEventKind<'a> {
+        TrackEventKind::Midi { channel, message }
+    }
+
+    /// Get the full status byte of this event, as it would be written to a file without running
+    /// status.
+    ///
+    /// For MIDI messages this includes the channel, in the range `0x80 ..= 0xEF`.
+    /// SysEx events yield `0xF0`, escapes yield `0xF7` and meta messages yield `0xFF`.
+    #[inline]
+    pub fn status_byte(&self) -> u8 {
+        match self {
+            TrackEventKind::Midi { channel, message } => {
+                message.status_nibble() << 4 | channel.as_int()
+            }
+            TrackEventKind::SysEx(_) => 0xF0,
+            TrackEventKind::Escape(_) => 0xF7,
+            TrackEventKind::Meta(_) => 0xFF,
+        }
+    }
+
+    /// Get the data bytes of a SysEx event, without the terminating `0xF7` byte (if present).
+    ///
+    /// Returns `None` if this is not a `SysEx` event.
+    /// Note that the first packet of a SysEx message split into several packets has no
+    /// terminating `0xF7` byte, so its data is returned whole.
+    #[inline]
+    pub fn sysex_payload(&self) -> Option<&'a [u8]> {
+        match *self {
+            TrackEventKind::SysEx(data) => Some(match data.split_last() {
+                Some((0xF7, payload)) => payload,
+                _ => data,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Get the raw byte payload of any event that carries one, that is, SysEx events, escapes,
+    /// and the meta messages listed in [`MetaMessage::bytes`](enum.MetaMessage.html#method.bytes).
+    ///
+    /// SysEx data is returned as-is, including the terminating `0xF7` byte if present.
+    /// Returns `None` for MIDI messages and for meta messages with structured data.
+    #[inline]
+    pub fn bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            TrackEventKind::SysEx(data) | TrackEventKind::Escape(data) => Some(data),
+            TrackEventKind::Meta(meta) => meta.bytes(),
+            TrackEventKind::Midi { .. } => None,
+        }
+    }
+
     /// Lossy conversion from a track event to a live event.
     ///
     /// Only channel MIDI messages and not-split SysEx messages can be converted.
@@ -224,6 +391,11 @@ impl<'a> TrackEventKind<'a> {
         }
     }
 }
+impl<'a> From<MetaMessage<'a>> for TrackEventKind<'a> {
+    fn from(meta: MetaMessage<'a>) -> Self {
+        TrackEventKind::Meta(meta)
+    }
+}
 
 /// Represents a MIDI message, usually associated to a MIDI channel.
 ///
@@ -349,8 +521,10 @@ impl MidiMessage {
         };
         (channel, msg)
     }
-    /// Get the raw status nibble for this MIDI message type.
-    pub(crate) fn status_nibble(&self) -> u8 {
+    /// Get the raw status nibble for this MIDI message type, that is, the high nibble of its
+    /// status byte, in the range `0x8 ..= 0xE`.
+    #[inline]
+    pub fn status_nibble(&self) -> u8 {
         match self {
             MidiMessage::NoteOff { .. } => 0x8,
             MidiMessage::NoteOn { .. } => 0x9,
@@ -361,6 +535,41 @@ impl MidiMessage {
             MidiMessage::PitchBend { .. } => 0xE,
         }
     }
+    /// Get the type of this message, without any of its data.
+    ///
+    /// This is useful to count or filter messages by type without matching on every variant.
+    #[inline]
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            MidiMessage::NoteOff { .. } => MessageKind::NoteOff,
+            MidiMessage::NoteOn { .. } => MessageKind::NoteOn,
+            MidiMessage::Aftertouch { .. } => MessageKind::Aftertouch,
+            MidiMessage::Controller { .. } => MessageKind::Controller,
+            MidiMessage::ProgramChange { .. } => MessageKind::ProgramChange,
+            MidiMessage::ChannelAftertouch { .. } => MessageKind::ChannelAftertouch,
+            MidiMessage::PitchBend { .. } => MessageKind::PitchBend,
+        }
+    }
+    /// Shift the value of a `PitchBend` message by a signed amount, saturating at the ends of the
+    /// pitch bend range.
+    ///
+    /// The offset is relative to the current value, so an offset of 0 leaves the message
+    /// untouched, regardless of whether the bend is centered (at raw value `0x2000`) or not.
+    /// The resulting raw value is clamped into `0 ..= 0x3FFF`.
+    ///
+    /// Messages other than `PitchBend` are returned unchanged.
+    #[inline]
+    pub fn bend_offset(self, delta: i16) -> MidiMessage {
+        match self {
+            MidiMessage::PitchBend { bend } => {
+                let raw = (bend.0.as_int() as i32 + delta as i32).clamp(0, 0x3FFF);
+                MidiMessage::PitchBend {
+                    bend: PitchBend(u14::new(raw as u16)),
+                }
+            }
+            msg => msg,
+        }
+    }
     /// Write the data part of this message, not including the status.
     pub(crate) fn write<W: Write>(&self, out: &mut W) -> WriteResult<W> {
         match self {
@@ -379,6 +588,45 @@ impl MidiMessage {
         }
         Ok(())
     }
+
+    /// Encode this message as it would be sent on the given channel, including the status byte.
+    ///
+    /// Running status is never used, so the result is a complete, self-contained message.
+    /// This is useful to forward individual messages to different channels, as done for MPE.
+    ///
+    /// This method is only available with the `alloc` feature enabled.
+    #[cfg(feature = "alloc")]
+    pub fn channel_message_bytes(&self, channel: u4) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(3);
+        LiveEvent::Midi {
+            channel,
+            message: *self,
+        }
+        .write(&mut bytes)
+        .expect("writing to a vec cannot fail");
+        bytes
+    }
+}
+
+/// The type of a [`MidiMessage`](enum.MidiMessage.html), without any of its data.
+///
+/// Obtained through [`MidiMessage::kind`](enum.MidiMessage.html#method.kind).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+pub enum MessageKind {
+    /// A `NoteOff` message.
+    NoteOff,
+    /// A `NoteOn` message.
+    NoteOn,
+    /// An `Aftertouch` (polyphonic key pressure) message.
+    Aftertouch,
+    /// A `Controller` message.
+    Controller,
+    /// A `ProgramChange` message.
+    ProgramChange,
+    /// A `ChannelAftertouch` message.
+    ChannelAftertouch,
+    /// A `PitchBend` message.
+    PitchBend,
 }
 
 /// The value of a pitch bend, represented as 14 bits.
@@ -412,7 +660,7 @@ impl PitchBend {
     /// Integers outside this range will be clamped.
     #[inline]
     pub fn from_int(int: i16) -> PitchBend {
-        PitchBend(u14::new((int.max(-0x2000).min(0x1FFF) + 0x2000) as u16))
+        PitchBend(u14::new((int.clamp(-0x2000, 0x1FFF) + 0x2000) as u16))
     }
 
     /// Create a `PitchBend` value from a number in the range `[-1.0, 1.0)`.
@@ -420,7 +668,7 @@ impl PitchBend {
     /// Floats outside this range will be clamped.
     #[inline]
     pub fn from_f32(float: f32) -> PitchBend {
-        PitchBend::from_int((float.max(-1.0).min(1.0) * 0x2000 as f32) as i16)
+        PitchBend::from_int((float.clamp(-1.0, 1.0) * 0x2000 as f32) as i16)
     }
 
     /// Create a `PitchBend` value from a number in the range `[-1.0, 1.0)`.
@@ -428,7 +676,7 @@ impl PitchBend {
     /// Floats outside this range will be clamped.
     #[inline]
     pub fn from_f64(float: f64) -> PitchBend {
-        PitchBend::from_int((float.max(-1.0).min(1.0) * 0x2000 as f64) as i16)
+        PitchBend::from_int((float.clamp(-1.0, 1.0) * 0x2000 as f64) as i16)
     }
 
     /// Returns an int in the range `[-0x2000, 0x1FFF]`.
@@ -537,6 +785,141 @@ impl<'a> MetaMessage<'a> {
         }
     }
 
+    /// Build a `TimeSignature` meta message out of a plain numerator and denominator, such as
+    /// `3` and `4` for 3/4 time.
+    ///
+    /// The denominator is stored as a power of two in the message, so only powers of two up to
+    /// 128 are accepted.
+    /// `clocks_per_click` is the amount of MIDI clocks per metronome click, and
+    /// `notes_32nd_per_quarter` the amount of 32nd notes per quarter note; usual values are 24
+    /// and 8 respectively.
+    ///
+    /// Returns `None` if the denominator is not a power of two, or is larger than 128.
+    #[inline]
+    pub fn time_signature_from(
+        num: u8,
+        denom: u8,
+        clocks_per_click: u8,
+        notes_32nd_per_quarter: u8,
+    ) -> Option<MetaMessage<'a>> {
+        if !denom.is_power_of_two() {
+            return None;
+        }
+        Some(MetaMessage::TimeSignature(
+            num,
+            denom.trailing_zeros() as u8,
+            clocks_per_click,
+            notes_32nd_per_quarter,
+        ))
+    }
+
+    /// Build a `KeySignature` meta message out of the amount of sharps (if positive) or flats (if
+    /// negative) in the key, and whether the key is minor.
+    ///
+    /// Returns `None` if the amount of sharps or flats is outside the `-7 ..= 7` range.
+    #[inline]
+    pub fn key_signature_from(sharps: i8, minor: bool) -> Option<MetaMessage<'a>> {
+        if (-7..=7).contains(&sharps) {
+            Some(MetaMessage::KeySignature(sharps, minor))
+        } else {
+            None
+        }
+    }
+
+    /// Build a `SmpteOffset` meta message out of an SMPTE time.
+    ///
+    /// Since `SmpteTime` can only hold valid times, this never fails.
+    #[inline]
+    pub fn smpte_offset(time: SmpteTime) -> MetaMessage<'a> {
+        MetaMessage::SmpteOffset(time)
+    }
+
+    /// Get the raw bytes of a text meta message, such as `Text`, `TrackName` or `Lyric`.
+    ///
+    /// Returns `None` if this is not one of the text meta messages (`0x01 ..= 0x09`).
+    #[inline]
+    pub fn text_bytes(&self) -> Option<&'a [u8]> {
+        use self::MetaMessage::*;
+        match *self {
+            Text(text) | Copyright(text) | TrackName(text) | InstrumentName(text) | Lyric(text)
+            | Marker(text) | CuePoint(text) | ProgramName(text) | DeviceName(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Get the raw byte payload of any meta message that carries one, that is, text meta
+    /// messages, `SequencerSpecific` and `Unknown` meta messages.
+    ///
+    /// This is useful to handle the payload of all of these messages uniformly, without matching
+    /// on every variant.
+    /// Returns `None` for meta messages with structured data, such as `Tempo`, even though they
+    /// are stored as bytes in the file.
+    #[inline]
+    pub fn bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            MetaMessage::SequencerSpecific(data) | MetaMessage::Unknown(_, data) => Some(data),
+            _ => self.text_bytes(),
+        }
+    }
+
+    /// Decode the text of a text meta message, such as `Text`, `TrackName` or `Lyric`.
+    ///
+    /// The SMF spec does not specify a text encoding, so the text is decoded as UTF-8 if it is
+    /// valid UTF-8, and as Latin-1 otherwise.
+    /// Since every byte is a valid Latin-1 character, decoding never fails.
+    /// To decode text in other encodings, such as the Shift-JIS used by many Japanese programs, see
+    /// [`text_with`](#method.text_with).
+    ///
+    /// Returns `None` if this is not one of the text meta messages (`0x01 ..= 0x09`).
+    ///
+    /// This method is only available with the `alloc` feature enabled.
+    #[cfg(feature = "alloc")]
+    pub fn text(&self) -> Option<Cow<'a, str>> {
+        let bytes = self.text_bytes()?;
+        Some(match core::str::from_utf8(bytes) {
+            Ok(text) => Cow::Borrowed(text),
+            Err(_) => Cow::Owned(bytes.iter().map(|&b| b as char).collect()),
+        })
+    }
+
+    /// Decode the text of a text meta message, such as `Text`, `TrackName` or `Lyric`, in the
+    /// given encoding.
+    ///
+    /// Malformed sequences are replaced with the replacement character (`U+FFFD`), so decoding
+    /// never fails.
+    /// Byte order marks are not treated specially.
+    /// The encodings are provided by the [`encoding_rs`](https://docs.rs/encoding_rs) crate,
+    /// re-exported as `midly::encoding_rs`:
+    ///
+    /// ```rust
+    /// use midly::{encoding_rs::SHIFT_JIS, MetaMessage};
+    ///
+    /// let name = MetaMessage::TrackName(&[0x83, 0x73, 0x83, 0x41, 0x83, 0x6D]);
+    /// assert_eq!(name.text_with(SHIFT_JIS).unwrap(), "ピアノ");
+    /// ```
+    ///
+    /// Returns `None` if this is not one of the text meta messages (`0x01 ..= 0x09`).
+    ///
+    /// This method is only available with the `encoding` feature enabled.
+    #[cfg(feature = "encoding")]
+    pub fn text_with(&self, encoding: &'static encoding_rs::Encoding) -> Option<Cow<'a, str>> {
+        let bytes = self.text_bytes()?;
+        Some(encoding.decode_without_bom_handling(bytes).0)
+    }
+
+    /// Split the data of a `SequencerSpecific` meta message into the manufacturer ID it starts
+    /// with and the remaining payload.
+    ///
+    /// Returns `None` if this is not a `SequencerSpecific` message, or if its data does not start
+    /// with a valid manufacturer ID.
+    #[inline]
+    pub fn manufacturer_id(&self) -> Option<(ManufacturerId, &'a [u8])> {
+        match *self {
+            MetaMessage::SequencerSpecific(data) => ManufacturerId::read(data),
+            _ => None,
+        }
+    }
+
     #[allow(clippy::len_zero)]
     fn read(raw: &mut &'a [u8]) -> Result<MetaMessage<'a>> {
         let type_byte = u8::read(raw).context(err_invalid!("failed to read meta message type"))?;
@@ -566,14 +949,27 @@ impl<'a> MetaMessage<'a> {
             0x54 if data.len() >= 5 => MetaMessage::SmpteOffset(
                 SmpteTime::read(&mut data).context(err_invalid!("failed to read smpte time"))?,
             ),
-            0x58 if data.len() >= 4 => MetaMessage::TimeSignature(
-                u8::read(&mut data)?,
-                u8::read(&mut data)?,
-                u8::read(&mut data)?,
-                u8::read(&mut data)?,
-            ),
+            0x58 if data.len() >= 4 => {
+                let num = u8::read(&mut data)?;
+                let den = u8::read(&mut data)?;
+                if cfg!(feature = "strict") {
+                    //The denominator is a power of 2, anything above 2^7 is surely corrupted
+                    ensure!(
+                        den <= 7,
+                        err_malformed!("time signature denominator out of range")
+                    );
+                }
+                MetaMessage::TimeSignature(num, den, u8::read(&mut data)?, u8::read(&mut data)?)
+            }
             0x59 => {
-                MetaMessage::KeySignature(u8::read(&mut data)? as i8, u8::read(&mut data)? != 0)
+                let sharps = u8::read(&mut data)? as i8;
+                if cfg!(feature = "strict") {
+                    ensure!(
+                        (-7..=7).contains(&sharps),
+                        err_malformed!("key signature sharps/flats out of range")
+                    );
+                }
+                MetaMessage::KeySignature(sharps, u8::read(&mut data)? != 0)
             }
             0x7F => MetaMessage::SequencerSpecific(data),
             _ => MetaMessage::Unknown(type_byte, data),
@@ -620,3 +1016,81 @@ impl<'a> MetaMessage<'a> {
         }
     }
 }
+
+/// A manufacturer ID, as found at the start of SysEx and sequencer-specific data.
+///
+/// IDs are either a single byte, or three bytes starting with `0x00`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum ManufacturerId {
+    /// A single-byte ID, which cannot be `0x00`.
+    Short(u7),
+    /// An extended ID, made up of the two bytes after the leading `0x00` byte.
+    Extended(u7, u7),
+}
+impl ManufacturerId {
+    /// Some well-known manufacturer IDs, along with their names.
+    pub const KNOWN: &'static [(ManufacturerId, &'static str)] = &[
+        (ManufacturerId::Short(u7::new(0x01)), "Sequential Circuits"),
+        (ManufacturerId::Short(u7::new(0x04)), "Moog"),
+        (ManufacturerId::Short(u7::new(0x07)), "Kurzweil"),
+        (ManufacturerId::Short(u7::new(0x0F)), "Ensoniq"),
+        (ManufacturerId::Short(u7::new(0x10)), "Oberheim"),
+        (ManufacturerId::Short(u7::new(0x18)), "E-mu"),
+        (ManufacturerId::Short(u7::new(0x3A)), "Steinberg"),
+        (ManufacturerId::Short(u7::new(0x40)), "Kawai"),
+        (ManufacturerId::Short(u7::new(0x41)), "Roland"),
+        (ManufacturerId::Short(u7::new(0x42)), "Korg"),
+        (ManufacturerId::Short(u7::new(0x43)), "Yamaha"),
+        (ManufacturerId::Short(u7::new(0x44)), "Casio"),
+        (ManufacturerId::Short(u7::new(0x47)), "Akai"),
+        (ManufacturerId::Short(u7::new(0x7D)), "Non-commercial"),
+        (
+            ManufacturerId::Extended(u7::new(0x00), u7::new(0x0E)),
+            "Alesis",
+        ),
+        (
+            ManufacturerId::Extended(u7::new(0x20), u7::new(0x29)),
+            "Novation",
+        ),
+        (
+            ManufacturerId::Extended(u7::new(0x20), u7::new(0x32)),
+            "Behringer",
+        ),
+        (
+            ManufacturerId::Extended(u7::new(0x20), u7::new(0x33)),
+            "Access Music",
+        ),
+        (
+            ManufacturerId::Extended(u7::new(0x20), u7::new(0x6B)),
+            "Arturia",
+        ),
+        (
+            ManufacturerId::Extended(u7::new(0x21), u7::new(0x09)),
+            "Native Instruments",
+        ),
+    ];
+
+    /// Read the manufacturer ID at the start of some data, returning it along with the rest of
+    /// the data.
+    ///
+    /// Returns `None` if the data is too short or starts with a byte that has its top bit set.
+    pub fn read(data: &[u8]) -> Option<(ManufacturerId, &[u8])> {
+        match *data {
+            [0x00, a, b, ref rest @ ..] => Some((
+                ManufacturerId::Extended(u7::try_from(a)?, u7::try_from(b)?),
+                rest,
+            )),
+            [0x00, ..] => None,
+            [id, ref rest @ ..] => Some((ManufacturerId::Short(u7::try_from(id)?), rest)),
+            [] => None,
+        }
+    }
+
+    /// Look up the name of this manufacturer in the [`KNOWN`](#associatedconstant.KNOWN) table.
+    pub fn name(&self) -> Option<&'static str> {
+        ManufacturerId::KNOWN
+            .iter()
+            .find(|(id, _)| id == self)
+            .map(|&(_, name)| name)
+    }
+}
@@ -5,7 +5,7 @@
 //! Besides, `write` methods that work with `midly::io::Write` types usually provide a `write_std`
 //! variant that works with `std::io::Write` types when the `std` feature is enabled.
 
-use crate::prelude::*;
+use crate::internal::*;
 
 /// Either `Ok(())` or the error specific to the `W` writer.
 pub type WriteResult<W> = StdResult<(), <W as Write>::Error>;
@@ -42,6 +42,22 @@ pub trait Seek: Write {
     fn write_at(&mut self, buf: &[u8], pos: u64) -> WriteResult<Self>;
 }
 
+/// Write a big-endian `u16` integer, as used by the fields of the `MThd` header chunk.
+///
+/// Useful when writing custom chunks.
+#[inline]
+pub fn write_u16_be<W: Write>(value: u16, out: &mut W) -> WriteResult<W> {
+    out.write(&value.to_be_bytes())
+}
+
+/// Write a big-endian `u32` integer, as used by the length of every chunk.
+///
+/// Useful when writing custom chunks.
+#[inline]
+pub fn write_u32_be<W: Write>(value: u32, out: &mut W) -> WriteResult<W> {
+    out.write(&value.to_be_bytes())
+}
+
 #[derive(Copy, Clone, Debug)]
 enum Never {}
 
@@ -91,7 +107,7 @@ impl<W: Write> Seek for NotSeekable<W> {
     }
 }
 
-impl<'a, W: Write> Write for &'a mut W {
+impl<W: Write> Write for &mut W {
     type Error = W::Error;
     type Seekable = W::Seekable;
     #[inline]
@@ -153,7 +169,7 @@ pub struct Cursor<'a> {
 impl<'a> Cursor<'a> {
     /// Create a new cursor located at the start of the given buffer.
     #[inline]
-    pub fn new(buffer: &mut [u8]) -> Cursor {
+    pub fn new(buffer: &mut [u8]) -> Cursor<'_> {
         Cursor {
             buf: buffer,
             cur: 0,
@@ -166,7 +182,7 @@ impl<'a> Cursor<'a> {
     ///
     /// Panics if `cursor > buffer.len()`.
     #[inline]
-    pub fn from_parts(buffer: &mut [u8], cursor: usize) -> Cursor {
+    pub fn from_parts(buffer: &mut [u8], cursor: usize) -> Cursor<'_> {
         assert!(
             cursor <= buffer.len(),
             "cursor beyond the end of the buffer"
@@ -292,7 +308,7 @@ pub enum CursorError {
     /// The input SMF was invalid.
     InvalidInput(&'static str),
 }
-impl<'a> Write for &'a mut [u8] {
+impl Write for &mut [u8] {
     type Error = CursorError;
     type Seekable = NotSeekable<Self>;
     #[inline]
@@ -303,7 +319,7 @@ impl<'a> Write for &'a mut [u8] {
             Err(CursorError::OutOfSpace)
         } else {
             self[..buf.len()].copy_from_slice(buf);
-            let slice = mem::replace(self, &mut []);
+            let slice = mem::take(self);
             *self = &mut slice[buf.len()..];
             Ok(())
         }
@@ -0,0 +1,403 @@
+//! Composable iterator adapters over the events of a track.
+//!
+//! The adapters in this module are named types, so that they can be stored in struct fields and
+//! named in function signatures.
+//! Except for [`Notes`](struct.Notes.html), which has to keep track of the notes that are still
+//! playing, none of them allocate, so they are available in `no_std` environments.
+//!
+//! The starting point is [`AbsoluteTime`](struct.AbsoluteTime.html), which turns an iterator over
+//! borrowed track events (such as `track.iter()`) into an iterator over `(tick, kind)` pairs,
+//! where `tick` is the absolute time of the event measured in MIDI ticks from the start of the
+//! track.
+//! The rest of the adapters work on `(tick, kind)` pairs, so they can be chained in any order:
+//!
+//! ```rust
+//! # #[cfg(feature = "alloc")] {
+//! use midly::{iter::{AbsoluteTime, ChannelFilter, Notes}, num::u4, TrackEvent};
+//!
+//! fn notes_on_channel(track: &[TrackEvent], channel: u4) {
+//!     let events = ChannelFilter::new(AbsoluteTime::new(track.iter()), channel);
+//!     for note in Notes::new(events) {
+//!         println!("key {} from tick {} to {}", note.key, note.start, note.end);
+//!     }
+//! }
+//! # notes_on_channel(&[], 0.into());
+//! # }
+//! ```
+//!
+//! The common case of iterating over the notes of a whole track is also available as the
+//! [`notes`](fn.notes.html) function, and the silences between them as the
+//! [`rests`](fn.rests.html) function.
+//!
+//! See also [`Routed`](../struct.Routed.html), which attaches port and channel information to
+//! track events.
+
+#[cfg(feature = "alloc")]
+use crate::event::MidiMessage;
+use crate::{
+    event::{TrackEvent, TrackEventKind},
+    internal::*,
+};
+#[cfg(feature = "alloc")]
+use alloc::collections::{BTreeMap, VecDeque};
+use core::iter::FusedIterator;
+#[cfg(feature = "alloc")]
+use core::slice;
+
+/// Iterate over the notes of a track, in order of their start time.
+///
+/// This is a shorthand for `Notes::new(AbsoluteTime::new(track.iter()))`.
+/// See [`Notes`](struct.Notes.html) for details on how notes are paired, which works correctly
+/// even with several channels interleaved in the same track, or with overlapping notes of the
+/// same key.
+///
+/// This function is only available with the `alloc` feature enabled.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn notes<'a, 'b>(
+    track: &'b [TrackEvent<'a>],
+) -> Notes<AbsoluteTime<slice::Iter<'b, TrackEvent<'a>>>> {
+    Notes::new(AbsoluteTime::new(track.iter()))
+}
+
+/// Iterate over the rests on a single channel of a track, that is, the gaps between notes where no
+/// note is sounding on that channel.
+///
+/// This is a shorthand for
+/// `Rests::new(Notes::new(ChannelFilter::new(AbsoluteTime::new(track.iter()), channel)))`.
+/// See [`Rests`](struct.Rests.html) for details.
+///
+/// This function is only available with the `alloc` feature enabled.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn rests<'a, 'b>(
+    track: &'b [TrackEvent<'a>],
+    channel: u4,
+) -> Rests<Notes<ChannelFilter<AbsoluteTime<slice::Iter<'b, TrackEvent<'a>>>>>> {
+    Rests::new(Notes::new(ChannelFilter::new(
+        AbsoluteTime::new(track.iter()),
+        channel,
+    )))
+}
+
+/// An iterator adapter that attaches the absolute time of each event, in MIDI ticks since the
+/// start of the track.
+///
+/// Wraps an iterator over borrowed track events, and yields `(tick, kind)` pairs.
+#[derive(Clone, Debug)]
+pub struct AbsoluteTime<I> {
+    inner: I,
+    tick: u64,
+}
+impl<I> AbsoluteTime<I> {
+    /// Wrap an iterator over track events, starting at tick 0.
+    #[inline]
+    pub fn new(events: I) -> AbsoluteTime<I> {
+        AbsoluteTime {
+            inner: events,
+            tick: 0,
+        }
+    }
+}
+impl<'a, 'b, I> Iterator for AbsoluteTime<I>
+where
+    'a: 'b,
+    I: Iterator<Item = &'b TrackEvent<'a>>,
+{
+    type Item = (u64, TrackEventKind<'a>);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let ev = self.inner.next()?;
+        self.tick += ev.delta.as_int() as u64;
+        Some((self.tick, ev.kind))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<'a, 'b, I> FusedIterator for AbsoluteTime<I>
+where
+    'a: 'b,
+    I: FusedIterator<Item = &'b TrackEvent<'a>>,
+{
+}
+
+/// An iterator adapter that only keeps MIDI events, discarding SysEx, escape and meta events.
+///
+/// Works on `(tick, kind)` pairs, such as the ones produced by
+/// [`AbsoluteTime`](struct.AbsoluteTime.html).
+#[derive(Clone, Debug)]
+pub struct MidiOnly<I> {
+    inner: I,
+}
+impl<I> MidiOnly<I> {
+    /// Wrap an iterator over `(tick, kind)` pairs.
+    #[inline]
+    pub fn new(events: I) -> MidiOnly<I> {
+        MidiOnly { inner: events }
+    }
+}
+impl<'a, I> Iterator for MidiOnly<I>
+where
+    I: Iterator<Item = (u64, TrackEventKind<'a>)>,
+{
+    type Item = (u64, TrackEventKind<'a>);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .find(|(_, kind)| matches!(kind, TrackEventKind::Midi { .. }))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+/// An iterator adapter that only keeps the MIDI events on a single channel.
+///
+/// Works on `(tick, kind)` pairs, such as the ones produced by
+/// [`AbsoluteTime`](struct.AbsoluteTime.html).
+/// Since non-MIDI events have no channel, they are discarded.
+#[derive(Clone, Debug)]
+pub struct ChannelFilter<I> {
+    inner: I,
+    channel: u4,
+}
+impl<I> ChannelFilter<I> {
+    /// Wrap an iterator over `(tick, kind)` pairs, keeping only the events on `channel`.
+    #[inline]
+    pub fn new(events: I, channel: u4) -> ChannelFilter<I> {
+        ChannelFilter {
+            inner: events,
+            channel,
+        }
+    }
+}
+impl<'a, I> Iterator for ChannelFilter<I>
+where
+    I: Iterator<Item = (u64, TrackEventKind<'a>)>,
+{
+    type Item = (u64, TrackEventKind<'a>);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let channel = self.channel;
+        self.inner.find(
+            |(_, kind)| matches!(kind, TrackEventKind::Midi { channel: ch, .. } if *ch == channel),
+        )
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+/// A note, made out of a `NoteOn` event paired with its release.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Note {
+    /// The absolute tick at which the note starts.
+    pub start: u64,
+    /// The absolute tick at which the note is released.
+    pub end: u64,
+    /// The channel the note is played on.
+    pub channel: u4,
+    /// The key of the note.
+    pub key: u7,
+    /// The velocity of the `NoteOn` event that started the note.
+    pub vel: u7,
+}
+impl Note {
+    /// The length of the note, in MIDI ticks.
+    #[inline]
+    pub fn duration(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+/// An iterator adapter that pairs each `NoteOn` event with its release, yielding whole
+/// [`Note`](struct.Note.html)s in order of their start time.
+///
+/// Works on `(tick, kind)` pairs, such as the ones produced by
+/// [`AbsoluteTime`](struct.AbsoluteTime.html).
+/// A note is released by a `NoteOff` event or by a `NoteOn` event with a velocity of zero, on the
+/// same channel and key.
+/// If the same key is started again before being released, the releases are matched first-in
+/// first-out: the first release ends the oldest note.
+/// This is the same rule used by [`edit::humanize`](../edit/fn.humanize.html) and
+/// [`edit::quantize_durations`](../edit/fn.quantize_durations.html).
+/// Releases that do not match any playing note are ignored, and notes that are never released end
+/// at the time of the last event.
+///
+/// The wrapped iterator is consumed in a single pass.
+/// A note is only yielded once it and all of the notes that started before it have been released,
+/// so notes are buffered for as long as an earlier note keeps playing.
+///
+/// This type is only available with the `alloc` feature enabled.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct Notes<I> {
+    inner: I,
+    /// The notes that were started but not yielded yet, in order of their start time, along with
+    /// whether they were released.
+    pending: VecDeque<(Note, bool)>,
+    /// The number of notes yielded so far, which is the index of the first pending note.
+    yielded: u64,
+    /// The indices of the notes that are playing, in order of their start time, by channel and
+    /// key.
+    playing: BTreeMap<(u8, u8), VecDeque<u64>>,
+    /// The tick of the last event read.
+    tick: u64,
+    /// Whether the wrapped iterator ran out of events.
+    done: bool,
+}
+#[cfg(feature = "alloc")]
+impl<I> Notes<I> {
+    /// Wrap an iterator over `(tick, kind)` pairs.
+    #[inline]
+    pub fn new(events: I) -> Notes<I> {
+        Notes {
+            inner: events,
+            pending: VecDeque::new(),
+            yielded: 0,
+            playing: BTreeMap::new(),
+            tick: 0,
+            done: false,
+        }
+    }
+}
+#[cfg(feature = "alloc")]
+impl<'a, I> Iterator for Notes<I>
+where
+    I: Iterator<Item = (u64, TrackEventKind<'a>)>,
+{
+    type Item = Note;
+
+    fn next(&mut self) -> Option<Note> {
+        loop {
+            match self.pending.front() {
+                Some(&(_, released)) if released || self.done => {
+                    let (mut note, released) = self.pending.pop_front()?;
+                    if !released {
+                        note.end = self.tick;
+                    }
+                    self.yielded += 1;
+                    return Some(note);
+                }
+                None if self.done => return None,
+                _ => {}
+            }
+            let (tick, kind) = match self.inner.next() {
+                Some(ev) => ev,
+                None => {
+                    self.done = true;
+                    continue;
+                }
+            };
+            self.tick = tick;
+            let (channel, key, vel) = match kind {
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
+                } => (channel, key, vel),
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOff { key, .. },
+                } => (channel, key, u7::new(0)),
+                _ => continue,
+            };
+            let playing = self
+                .playing
+                .entry((channel.as_int(), key.as_int()))
+                .or_default();
+            if vel > 0 {
+                playing.push_back(self.yielded + self.pending.len() as u64);
+                self.pending.push_back((
+                    Note {
+                        start: tick,
+                        end: tick,
+                        channel,
+                        key,
+                        vel,
+                    },
+                    false,
+                ));
+            } else if let Some(idx) = playing.pop_front() {
+                let (note, released) = &mut self.pending[(idx - self.yielded) as usize];
+                note.end = tick;
+                *released = true;
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = self.pending.len();
+        let upper = self
+            .inner
+            .size_hint()
+            .1
+            .and_then(|upper| upper.checked_add(pending));
+        (if self.done { pending } else { 0 }, upper)
+    }
+}
+/// An iterator adapter that finds the rests between notes, yielding `(start, len)` pairs with the
+/// absolute tick at which each rest starts and its length in ticks.
+///
+/// Works on [`Note`](struct.Note.html)s in order of their start time, such as the ones produced by
+/// [`Notes`](struct.Notes.html).
+/// A rest starts when all notes started so far have been released, and ends when the next note
+/// starts.
+/// Overlapping or back-to-back notes (legato) therefore produce no rest.
+/// Only the gaps between notes are considered, so there are no rests before the first note or
+/// after the last one.
+///
+/// Usually the notes are restricted to a single channel with
+/// [`ChannelFilter`](struct.ChannelFilter.html) beforehand, since otherwise notes on other
+/// channels fill the gaps.
+#[derive(Clone, Debug)]
+pub struct Rests<I> {
+    inner: I,
+    /// The tick at which the last of the notes seen so far is released.
+    end: Option<u64>,
+}
+impl<I> Rests<I> {
+    /// Wrap an iterator over notes.
+    #[inline]
+    pub fn new(notes: I) -> Rests<I> {
+        Rests {
+            inner: notes,
+            end: None,
+        }
+    }
+}
+impl<I> Iterator for Rests<I>
+where
+    I: Iterator<Item = Note>,
+{
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<(u64, u64)> {
+        loop {
+            let note = self.inner.next()?;
+            let rest = match self.end {
+                Some(end) if note.start > end => Some((end, note.start - end)),
+                _ => None,
+            };
+            self.end = Some(self.end.map_or(note.end, |end| end.max(note.end)));
+            if rest.is_some() {
+                return rest;
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
@@ -162,6 +162,20 @@
 //!   By enabling the `strict` feature the parser will reject uncompliant data and do
 //!   additional checking, throwing errors of the kind
 //!   [`ErrorKind::Malformed`](enum.ErrorKind.html#variant.Malformed) when such a situation arises.
+//!
+//! - `test-util`
+//!
+//!   This feature enables the [`test_util`](test_util/index.html) module, which generates small
+//!   `.mid` files, so that crates depending on `midly` can write tests without binary fixtures.
+//!   It is meant to be enabled only in `dev-dependencies`.
+//!
+//! - `encoding`
+//!
+//!   This feature enables [`MetaMessage::text_with`](enum.MetaMessage.html#method.text_with), to
+//!   decode text meta messages in encodings other than UTF-8 and Latin-1, such as Shift-JIS.
+//!
+//!   Enabling this feature brings in the `encoding_rs` dependency, which is re-exported as
+//!   `midly::encoding_rs`.
 
 #![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![warn(missing_docs)]
@@ -186,14 +200,15 @@ macro_rules! ensure {
 #[macro_use]
 mod error;
 
+/// Common imports used throughout the crate.
 #[macro_use]
-mod prelude {
+mod internal {
     #[cfg(feature = "std")]
     pub(crate) use crate::io::IoWrap;
     pub(crate) use crate::{
         error::{ErrorKind, Result, ResultExt, StdResult},
         io::{Seek, Write, WriteCounter, WriteResult},
-        primitive::{u14, u24, u28, u4, u7, IntRead, IntReadBottom7, SplitChecked},
+        primitive::{u14, u15, u24, u28, u4, u7, IntRead, IntReadBottom7, SplitChecked},
     };
     #[cfg(feature = "alloc")]
     pub(crate) use alloc::{boxed::Box, vec, vec::Vec};
@@ -209,33 +224,92 @@ mod prelude {
     }
 }
 
+mod analysis;
 mod arena;
+mod dump;
+pub mod edit;
 mod event;
 pub mod io;
+pub mod iter;
 pub mod live;
 mod primitive;
 mod riff;
+mod route;
+mod rpn;
+mod schedule;
 mod smf;
 pub mod stream;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod warning;
 
 #[cfg(feature = "std")]
 pub use crate::smf::write_std;
 #[cfg(feature = "alloc")]
 pub use crate::{
+    analysis::FileClass,
     arena::Arena,
-    smf::{BytemappedTrack, Smf, SmfBytemap, Track},
+    schedule::Scheduler,
+    smf::{
+        roundtrip_equal, BytemappedTrack, ParseOptions, Preserved, Smf, SmfBytemap, Track,
+        UnknownChunk,
+    },
 };
 pub use crate::{
     error::{Error, ErrorKind, Result},
-    event::{MetaMessage, MidiMessage, PitchBend, TrackEvent, TrackEventKind},
+    event::{
+        ManufacturerId, MessageKind, MetaMessage, MidiMessage, PitchBend, RawEvent, TrackEvent,
+        TrackEventKind,
+    },
     primitive::{Format, Fps, SmpteTime, Timing},
-    smf::{parse, write, EventBytemapIter, EventIter, Header, TrackIter},
+    route::{Routed, Routing},
+    rpn::{Parameter, ParameterChange, PitchBendRange, RpnTracker},
+    smf::{
+        parse, write, write_with_options, EventBytemapIter, EventIter, FinishReason, Header,
+        RawEventIter, TrackIter, WriteOptions,
+    },
+    warning::{Warning, WarningKind},
 };
+#[cfg(feature = "encoding")]
+pub use encoding_rs;
 
 /// Exotically-sized integers used by the MIDI standard.
 pub mod num {
     pub use crate::primitive::{u14, u15, u24, u28, u4, u7};
 }
 
+/// Convenient re-exports of the most commonly used types.
+///
+/// Meant to be glob-imported:
+///
+/// ```
+/// use midly::prelude::*;
+///
+/// let ev = TrackEvent {
+///     delta: u28::new(0),
+///     kind: TrackEventKind::Midi {
+///         channel: u4::new(0),
+///         message: MidiMessage::NoteOn {
+///             key: u7::new(60),
+///             vel: u7::new(64),
+///         },
+///     },
+/// };
+/// # let _ = ev;
+/// ```
+///
+/// Only types are re-exported, so that glob-importing this module does not shadow common names
+/// such as `Result` or `std::io::Write`.
+pub mod prelude {
+    pub use crate::{
+        live::LiveEvent,
+        num::{u14, u15, u24, u28, u4, u7},
+        Format, Fps, Header, MetaMessage, MidiMessage, PitchBend, Timing, TrackEvent,
+        TrackEventKind,
+    };
+    #[cfg(feature = "alloc")]
+    pub use crate::{Smf, Track};
+}
+
 #[cfg(test)]
 mod test;
@@ -14,7 +14,7 @@
 //! Note that MIDI byte streams, which are not clearly delimited packets, must be parsed through
 //! the [`stream`](../stream/index.html) api.
 
-use crate::{event::MidiMessage, prelude::*};
+use crate::{event::MidiMessage, internal::*, primitive::Timing};
 #[cfg(feature = "alloc")]
 use crate::{event::TrackEventKind, Arena};
 
@@ -60,7 +60,7 @@ impl<'a> LiveEvent<'a> {
         Self::read(status, data)
     }
 
-    pub(crate) fn read(status: u8, data: &[u7]) -> Result<LiveEvent> {
+    pub(crate) fn read(status: u8, data: &[u7]) -> Result<LiveEvent<'_>> {
         match status {
             0x80..=0xEF => {
                 // MIDI message
@@ -234,7 +234,7 @@ impl<'a> SystemCommon<'a> {
         let ev = match status {
             0xF0 => {
                 //SysEx
-                SystemCommon::SysEx(&data[..])
+                SystemCommon::SysEx(data)
             }
             0xF1 if data.len() >= 1 => {
                 //MTC Quarter Frame
@@ -259,7 +259,7 @@ impl<'a> SystemCommon<'a> {
             }
             0xF1..=0xF5 => {
                 //Unknown system common event
-                SystemCommon::Undefined(status, &data[..])
+                SystemCommon::Undefined(status, data)
             }
             _ => {
                 //Invalid/Unknown/Unreachable event
@@ -292,6 +292,33 @@ impl<'a> SystemCommon<'a> {
         }
     }
 
+    /// Get the position carried by a `SongPosition` message, in MIDI beats (sixteenth notes).
+    ///
+    /// Returns `None` if this is not a `SongPosition` message.
+    #[inline]
+    pub fn song_position_beats(&self) -> Option<u16> {
+        match *self {
+            SystemCommon::SongPosition(pos) => Some(pos.as_int()),
+            _ => None,
+        }
+    }
+
+    /// Convert the position carried by a `SongPosition` message into ticks, given the timing of a
+    /// file.
+    ///
+    /// A MIDI beat is a sixteenth note, that is, a quarter of a beat as specified by
+    /// [`Timing::Metrical`](../enum.Timing.html#variant.Metrical).
+    /// The result is rounded down to the nearest tick.
+    ///
+    /// Returns `None` if this is not a `SongPosition` message, or if the timing is not metrical,
+    /// since song positions are only meaningful relative to a musical grid.
+    #[inline]
+    pub fn song_position_ticks(&self, timing: Timing) -> Option<u64> {
+        let beats = self.song_position_beats()? as u64;
+        let tpb = timing.ticks_per_beat()?.as_int() as u64;
+        Some(beats * tpb / 4)
+    }
+
     /// Remove any lifetimed data from this event to create a `SystemCommon` with `'static`
     /// lifetime that can be stored and moved everywhere, solving borrow checker issues.
     ///
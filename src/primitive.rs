@@ -2,7 +2,7 @@
 //! All primitives have a known, fixed size.
 //! Also, primitives advance the file pointer when read.
 
-use crate::prelude::*;
+use crate::internal::*;
 
 pub(crate) trait SplitChecked: Sized {
     fn split_checked(&mut self, at: usize) -> Option<Self>;
@@ -119,6 +119,15 @@ macro_rules! restricted_int {
         impl $name {
             const MASK: $inner = (1 << $bits) - 1;
 
+            /// The maximum value that this restricted integer can hold.
+            pub const MAX: $name = $name(Self::MASK);
+
+            /// The minimum value that this restricted integer can hold, which is always zero.
+            pub const MIN: $name = $name(0);
+
+            /// The amount of bits in this restricted integer.
+            pub const BITS: u32 = $bits;
+
             /// The maximum value that this restricted integer can hold.
             #[inline]
             pub const fn max_value() -> $name {
@@ -150,11 +159,32 @@ macro_rules! restricted_int {
                 }
             }
 
+            /// Iterate over every value that this restricted integer can hold, in increasing
+            /// order.
+            ///
+            /// This is useful to test a transformation exhaustively.
+            #[inline]
+            pub fn all() -> impl DoubleEndedIterator<Item = $name> + Clone {
+                (0..=Self::MASK).map($name)
+            }
+
+            /// Iterate over the values from `start` (inclusive) to `end` (exclusive), in
+            /// increasing order, like a `start..end` range.
+            #[inline]
+            pub fn range(
+                start: $name,
+                end: $name,
+            ) -> impl DoubleEndedIterator<Item = $name> + Clone {
+                (start.0..end.0).map($name)
+            }
+
             /// Get the inner integer out of the wrapper.
             /// The inner integer is guaranteed to be in range of the restricted wrapper.
+            ///
+            /// This is the way to get back a plain integer for arithmetic.
             #[inline]
-            pub fn as_int(self) -> $inner {
-                Into::into(self)
+            pub const fn as_int(self) -> $inner {
+                self.0
             }
 
             /// Cast a slice of raw integers to a slice of restricted integers, only if there are
@@ -450,7 +480,7 @@ pub(crate) fn read_varlen_slice<'a>(raw: &mut &'a [u8]) -> Result<&'a [u8]> {
             if cfg!(feature = "strict") {
                 bail!(err_malformed!("incomplete varlen slice"))
             } else {
-                mem::replace(raw, &[])
+                mem::take(raw)
             }
         }
     })
@@ -520,6 +550,72 @@ pub enum Timing {
     Timecode(Fps, u8),
 }
 impl Timing {
+    /// Get the amount of ticks per beat, if this is metrical timing.
+    #[inline]
+    pub fn ticks_per_beat(&self) -> Option<u15> {
+        match *self {
+            Timing::Metrical(tpb) => Some(tpb),
+            Timing::Timecode(..) => None,
+        }
+    }
+
+    /// Get the frames per second and the ticks per frame, if this is timecode timing.
+    #[inline]
+    pub fn timecode(&self) -> Option<(Fps, u8)> {
+        match *self {
+            Timing::Metrical(_) => None,
+            Timing::Timecode(fps, tpf) => Some((fps, tpf)),
+        }
+    }
+
+    /// Convert a duration in ticks into microseconds.
+    ///
+    /// For metrical timing, the `tempo` (in microseconds per beat, as specified by the
+    /// [`MetaMessage::Tempo`](enum.MetaMessage.html#Tempo.v) event) is required to know the
+    /// length of a tick.
+    /// For timecode timing the length of a tick is fixed, and `tempo` is ignored.
+    ///
+    /// The result is rounded down to the nearest microsecond.
+    /// Returns `None` if the timing has a resolution of zero ticks, or if the result does not fit
+    /// in a `u64`.
+    #[inline]
+    pub fn ticks_to_micros(&self, ticks: u64, tempo: u24) -> Option<u64> {
+        let ticks = ticks as u128;
+        let (num, den) = match *self {
+            Timing::Metrical(tpb) => (ticks * tempo.as_int() as u128, tpb.as_int() as u128),
+            Timing::Timecode(Fps::Fps29, tpf) => (ticks * 1_001_000_000, 30_000 * tpf as u128),
+            Timing::Timecode(fps, tpf) => (ticks * 1_000_000, fps.as_int() as u128 * tpf as u128),
+        };
+        if den == 0 {
+            return None;
+        }
+        u64::try_from(num / den).ok()
+    }
+
+    /// Convert a position in beats (quarter notes) since the start of the track into an absolute
+    /// tick, rounding to the nearest tick.
+    ///
+    /// Since rounding is done on absolute positions, converting every event of a track this way
+    /// and then computing the delta times (for example with
+    /// [`edit::sort_track`](edit/fn.sort_track.html)) never accumulates rounding errors, no matter
+    /// how many events there are.
+    ///
+    /// Returns `None` for timecode timing, which is not measured in beats, or if `beats` is
+    /// negative, not a number, or too large for the result to fit in a `u64`.
+    #[inline]
+    pub fn beats_to_ticks(&self, beats: f64) -> Option<u64> {
+        //Check before rounding, so that slightly negative positions are not rounded up to 0
+        if beats < 0.0 {
+            return None;
+        }
+        let ticks = beats * self.ticks_per_beat()?.as_int() as f64 + 0.5;
+        if ticks >= 0.0 && ticks < u64::MAX as f64 {
+            Some(ticks as u64)
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn read(raw: &mut &[u8]) -> Result<Timing> {
         let raw =
             u16::read(raw).context(err_invalid!("unexpected eof when reading midi timing"))?;
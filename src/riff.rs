@@ -2,7 +2,7 @@
 //! Support for these files is provided by unwrapping the input slice, stripping away the RIFF
 //! wrappers around the raw SMF file.
 
-use crate::prelude::*;
+use crate::internal::*;
 
 struct ChunkIter<'a>(&'a [u8]);
 impl<'a> Iterator for ChunkIter<'a> {
@@ -17,7 +17,7 @@ impl<'a> Iterator for ChunkIter<'a> {
             let len = u32::from_le_bytes(len);
             let data = match self.0.split_checked(len as usize) {
                 Some(data) => data,
-                None => mem::replace(&mut self.0, &[]),
+                None => mem::take(&mut self.0),
             };
             if len % 2 == 1 {
                 let _pad = self.0.split_checked(1);
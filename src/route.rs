@@ -0,0 +1,92 @@
+//! Interpretation of the obsolete `MidiPort` and `MidiChannel` meta events, which are still
+//! common in older multi-port files.
+
+use crate::{
+    event::{MetaMessage, TrackEvent, TrackEventKind},
+    internal::*,
+};
+
+/// The port and channel that an event is routed to.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, Default)]
+pub struct Routing {
+    /// The port set by the latest `MidiPort` meta event, if any.
+    pub port: Option<u7>,
+    /// The channel this event is associated with, if any.
+    ///
+    /// For MIDI events this is simply their own channel.
+    /// For other events, this is the channel set by a `MidiChannel` meta event, as long as no MIDI
+    /// event came in between.
+    pub channel: Option<u4>,
+}
+
+/// An iterator adapter that attaches the effective [`Routing`](struct.Routing.html) to each track
+/// event.
+///
+/// The `MidiPort` meta event sets the port for all subsequent events in the track.
+/// The `MidiChannel` meta event (also known as "channel prefix") associates all subsequent
+/// non-MIDI events (meta and SysEx events) with a channel, until the next MIDI event.
+///
+/// The routing reported alongside `MidiPort` and `MidiChannel` events already includes their own
+/// effect.
+///
+/// This type is always available, even in `no_std` environments.
+#[derive(Clone, Debug)]
+pub struct Routed<I> {
+    inner: I,
+    port: Option<u7>,
+    channel_prefix: Option<u4>,
+}
+impl<I> Routed<I> {
+    /// Wrap an iterator over track events, starting with no port or channel information.
+    #[inline]
+    pub fn new(events: I) -> Routed<I> {
+        Routed {
+            inner: events,
+            port: None,
+            channel_prefix: None,
+        }
+    }
+
+    /// The port set by the latest `MidiPort` meta event seen so far.
+    #[inline]
+    pub fn port(&self) -> Option<u7> {
+        self.port
+    }
+}
+impl<'a, 'b, I> Iterator for Routed<I>
+where
+    'a: 'b,
+    I: Iterator<Item = &'b TrackEvent<'a>>,
+{
+    type Item = (Routing, &'b TrackEvent<'a>);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let ev = self.inner.next()?;
+        let channel = match ev.kind {
+            TrackEventKind::Midi { channel, .. } => {
+                self.channel_prefix = None;
+                Some(channel)
+            }
+            TrackEventKind::Meta(MetaMessage::MidiPort(port)) => {
+                self.port = Some(port);
+                self.channel_prefix
+            }
+            TrackEventKind::Meta(MetaMessage::MidiChannel(channel)) => {
+                self.channel_prefix = Some(channel);
+                Some(channel)
+            }
+            _ => self.channel_prefix,
+        };
+        let routing = Routing {
+            port: self.port,
+            channel,
+        };
+        Some((routing, ev))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,187 @@
+//! Decoding of registered and non-registered parameter numbers (RPN and NRPN), which are spread
+//! across several `Controller` messages.
+
+use crate::{event::MidiMessage, internal::*, PitchBend};
+
+/// A parameter number, selected through the RPN or NRPN controllers.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Parameter {
+    /// A registered parameter, defined by the MIDI standard.
+    ///
+    /// For example, `0x0000` is the pitch bend sensitivity and `0x0002` is the coarse tuning.
+    Registered(u14),
+    /// A non-registered parameter, whose meaning is specific to each manufacturer.
+    NonRegistered(u14),
+}
+
+/// A change to the value of a parameter, reported by
+/// [`RpnTracker::feed`](struct.RpnTracker.html#method.feed).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct ParameterChange {
+    /// The channel in which the parameter changed.
+    pub channel: u4,
+    /// The parameter that changed.
+    pub parameter: Parameter,
+    /// The new 14-bit value of the parameter.
+    pub value: u14,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, Default)]
+struct ChannelParams {
+    registered: bool,
+    msb: Option<u7>,
+    lsb: Option<u7>,
+    value: u16,
+}
+impl ChannelParams {
+    fn select(&mut self, registered: bool, msb: Option<u7>, lsb: Option<u7>) {
+        if registered != self.registered {
+            self.msb = None;
+            self.lsb = None;
+        }
+        self.registered = registered;
+        self.msb = msb.or(self.msb);
+        self.lsb = lsb.or(self.lsb);
+        self.value = 0;
+    }
+
+    fn selected(&self) -> Option<Parameter> {
+        let (msb, lsb) = (self.msb?.as_int() as u16, self.lsb?.as_int() as u16);
+        if msb == 0x7F && lsb == 0x7F {
+            //The "null" parameter deselects the current parameter
+            return None;
+        }
+        let number = u14::new(msb << 7 | lsb);
+        Some(if self.registered {
+            Parameter::Registered(number)
+        } else {
+            Parameter::NonRegistered(number)
+        })
+    }
+}
+
+/// Keeps track of the parameter selected in each channel, decoding the data entry messages that
+/// change the value of registered and non-registered parameters.
+///
+/// In MIDI, parameters are selected with the `0x65`/`0x64` (RPN) or `0x63`/`0x62` (NRPN)
+/// controllers, and their value is then changed with the `0x06`/`0x26` (data entry) or
+/// `0x60`/`0x61` (data increment/decrement) controllers.
+/// Sending a data entry MSB resets the LSB of the value to zero.
+///
+/// This type is always available, even in `no_std` environments.
+#[derive(Clone, Debug, Default)]
+pub struct RpnTracker {
+    channels: [ChannelParams; 16],
+}
+impl RpnTracker {
+    /// Create a new tracker, with no parameters selected in any channel.
+    #[inline]
+    pub fn new() -> RpnTracker {
+        RpnTracker::default()
+    }
+
+    /// The parameter currently selected in the given channel, if any.
+    #[inline]
+    pub fn selected(&self, channel: u4) -> Option<Parameter> {
+        self.channels[channel.as_int() as usize].selected()
+    }
+
+    /// Process a MIDI message sent over the given channel, reporting the change if the message
+    /// modified the value of the selected parameter.
+    pub fn feed(&mut self, channel: u4, message: &MidiMessage) -> Option<ParameterChange> {
+        let (controller, value) = match *message {
+            MidiMessage::Controller { controller, value } => (controller.as_int(), value),
+            _ => return None,
+        };
+        let params = &mut self.channels[channel.as_int() as usize];
+        match controller {
+            0x65 => params.select(true, Some(value), None),
+            0x64 => params.select(true, None, Some(value)),
+            0x63 => params.select(false, Some(value), None),
+            0x62 => params.select(false, None, Some(value)),
+            0x06 | 0x26 | 0x60 | 0x61 => {
+                let parameter = params.selected()?;
+                let data = value.as_int() as u16;
+                params.value = match controller {
+                    0x06 => data << 7,
+                    0x26 => params.value & !0x7F | data,
+                    0x60 => (params.value + 1).min(0x3FFF),
+                    _ => params.value.saturating_sub(1),
+                };
+                return Some(ParameterChange {
+                    channel,
+                    parameter,
+                    value: u14::new(params.value),
+                });
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Keeps track of the pitch bend range of each channel, as set by the registered parameter `0`
+/// (pitch bend sensitivity).
+///
+/// The data entry MSB of the parameter specifies the range in semitones, and the LSB specifies
+/// additional cents.
+/// The range defaults to 2 semitones, as specified by the General MIDI standard.
+///
+/// This type is always available, even in `no_std` environments.
+#[derive(Clone, Debug)]
+pub struct PitchBendRange {
+    rpn: RpnTracker,
+    ranges: [(u8, u8); 16],
+}
+impl Default for PitchBendRange {
+    #[inline]
+    fn default() -> PitchBendRange {
+        PitchBendRange {
+            rpn: RpnTracker::new(),
+            ranges: [(2, 0); 16],
+        }
+    }
+}
+impl PitchBendRange {
+    /// Create a new decoder, with all channels set to the default range of 2 semitones.
+    #[inline]
+    pub fn new() -> PitchBendRange {
+        PitchBendRange::default()
+    }
+
+    /// Process a MIDI message sent over the given channel.
+    #[inline]
+    pub fn feed(&mut self, channel: u4, message: &MidiMessage) {
+        if let Some(ParameterChange {
+            parameter: Parameter::Registered(number),
+            value,
+            ..
+        }) = self.rpn.feed(channel, message)
+        {
+            if number == 0 {
+                let (semitones, cents) = (value.as_int() >> 7, value.as_int() & 0x7F);
+                self.ranges[channel.as_int() as usize] = (semitones as u8, cents as u8);
+            }
+        }
+    }
+
+    /// The current pitch bend range of the given channel, as a `(semitones, cents)` pair.
+    #[inline]
+    pub fn range(&self, channel: u4) -> (u8, u8) {
+        self.ranges[channel.as_int() as usize]
+    }
+
+    /// The current pitch bend range of the given channel, in (fractional) semitones.
+    #[inline]
+    pub fn semitones(&self, channel: u4) -> f32 {
+        let (semitones, cents) = self.range(channel);
+        semitones as f32 + cents as f32 / 100.0
+    }
+
+    /// Compute the actual bend in semitones produced by a `PitchBend` message in the given
+    /// channel, according to the current pitch bend range of the channel.
+    #[inline]
+    pub fn bend_semitones(&self, channel: u4, bend: PitchBend) -> f32 {
+        bend.as_f32() * self.semitones(channel)
+    }
+}
@@ -0,0 +1,103 @@
+//! Conversion of a whole file into a timeline of live events, ready to be played back.
+
+#![cfg(feature = "alloc")]
+
+use crate::{
+    edit,
+    event::{MetaMessage, TrackEventKind},
+    internal::*,
+    live::LiveEvent,
+    primitive::Format,
+    smf::Smf,
+};
+use core::time::Duration;
+
+/// An iterator over the events of a file, each one with the exact time at which it should be
+/// played, measured from the start of the file.
+///
+/// All tempo changes are resolved beforehand, so the time of every event is precomputed.
+/// Only events that can be sent to a device are yielded, that is, channel MIDI messages and
+/// SysEx messages (see [`TrackEventKind::as_live_event`](enum.TrackEventKind.html#method.as_live_event)).
+/// Meta events and escapes are used for their timing information and then discarded.
+///
+/// Files with metrical timing start at 120 beats per minute, until the first `Tempo` event.
+/// A different initial tempo can be assumed through
+/// [`Scheduler::with_initial_tempo`](#method.with_initial_tempo), which is useful for files that
+/// rely on an external tempo.
+/// Files with timecode timing have a fixed tick length, and ignore `Tempo` events altogether.
+///
+/// The tracks of `Parallel` and `SingleTrack` files are played simultaneously, while the tracks
+/// of `Sequential` files are played one after the other.
+///
+/// This type is only available with the `alloc` feature enabled.
+#[derive(Clone, Debug)]
+pub struct Scheduler<'a> {
+    events: vec::IntoIter<(Duration, LiveEvent<'a>)>,
+}
+impl<'a> Scheduler<'a> {
+    /// Resolve the playback time of all events in a file.
+    ///
+    /// Fails if the timing of the file has a resolution of zero ticks, or if the file is so long
+    /// that its duration in microseconds overflows a `u64`.
+    pub fn new(smf: &Smf<'a>) -> Result<Scheduler<'a>> {
+        Scheduler::with_initial_tempo(smf, u24::new(500_000))
+    }
+
+    /// Resolve the playback time of all events in a file, assuming the given tempo (in
+    /// microseconds per beat) until the first `Tempo` event.
+    ///
+    /// `Scheduler::new` is equivalent to using the standard default of 500000 microseconds per
+    /// beat, that is, 120 beats per minute.
+    pub fn with_initial_tempo(smf: &Smf<'a>, initial_tempo: u24) -> Result<Scheduler<'a>> {
+        let timing = smf.header.timing;
+        let to_micros = |ticks: u64, tempo: u24| {
+            timing
+                .ticks_to_micros(ticks, tempo)
+                .ok_or(err_invalid!("event time out of range"))
+        };
+        let sections = match smf.header.format {
+            Format::Sequential => smf.tracks.iter().map(|track| track.to_vec()).collect(),
+            Format::SingleTrack | Format::Parallel => vec![edit::merge(&smf.tracks)],
+        };
+        let mut tempo = initial_tempo;
+        // The time of the last tempo change, and the ticks elapsed since then
+        let mut base = 0;
+        let mut ticks = 0;
+        let mut events = Vec::new();
+        for section in sections {
+            for ev in section {
+                ticks += ev.delta.as_int() as u64;
+                let micros = base + to_micros(ticks, tempo)?;
+                match ev.kind {
+                    TrackEventKind::Meta(MetaMessage::Tempo(new_tempo)) => {
+                        // Restart counting from here, so that rounding errors do not accumulate
+                        base = micros;
+                        ticks = 0;
+                        tempo = new_tempo;
+                    }
+                    kind => {
+                        if let Some(live) = kind.as_live_event() {
+                            events.push((Duration::from_micros(micros), live));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Scheduler {
+            events: events.into_iter(),
+        })
+    }
+}
+impl<'a> Iterator for Scheduler<'a> {
+    type Item = (Duration, LiveEvent<'a>);
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.next()
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.events.size_hint()
+    }
+}
+impl ExactSizeIterator for Scheduler<'_> {}
+impl core::iter::FusedIterator for Scheduler<'_> {}
@@ -1,11 +1,14 @@
 //! Specific to the SMF packaging of MIDI streams.
 
+#[cfg(feature = "alloc")]
+use crate::warning::{self, Warning};
 use crate::{
-    event::TrackEvent,
-    prelude::*,
+    event::{MetaMessage, MidiMessage, RawEvent, TrackEvent, TrackEventKind},
+    internal::*,
     primitive::{Format, Timing},
     riff,
 };
+use core::iter::FusedIterator;
 
 /// How many events per byte to estimate when allocating memory for events while parsing.
 ///
@@ -53,6 +56,12 @@ pub type Track<'a> = Vec<TrackEvent<'a>>;
 /// Represents a single `.mid` Standard Midi File.
 /// If you're casually looking to parse a `.mid` file, this is the type you're looking for.
 ///
+/// Equality and hashing are structural: two files are equal if their headers are equal and
+/// their tracks contain the same events, with the same delta times.
+/// Encoding details that do not survive parsing, such as the use of running status or unknown
+/// chunks, are not taken into account.
+/// This makes `Smf` suitable as a key for caching the results of processing a file.
+///
 /// This type is only available with the `alloc` feature enabled.
 /// If you're looking for a fully `no_std` alternative, see the [`parse`](fn.parse.html) function.
 #[cfg(feature = "alloc")]
@@ -76,16 +85,383 @@ impl<'a> Smf<'a> {
         }
     }
 
+    /// Create a `Smf` out of a header and its tracks.
+    ///
+    /// This is the inverse of [`into_parts`](#method.into_parts).
+    /// Note that the header format is not checked against the amount of tracks.
+    #[inline]
+    pub fn from_parts(header: Header, tracks: Vec<Track<'a>>) -> Smf<'a> {
+        Smf { header, tracks }
+    }
+
+    /// Split this `Smf` into its header and its tracks, taking ownership of the tracks without
+    /// cloning them.
+    #[inline]
+    pub fn into_parts(self) -> (Header, Vec<Track<'a>>) {
+        (self.header, self.tracks)
+    }
+
+    /// Create a minimal valid `Smf`, with a single track containing only an `EndOfTrack` event.
+    ///
+    /// The file has the given format and metrical timing of `tpq` ticks per beat (quarter note).
+    /// Values of `tpq` larger than `0x7FFF` are clamped, since the timing is a 15-bit integer.
+    #[inline]
+    pub fn empty(format: Format, tpq: u16) -> Smf<'a> {
+        Smf {
+            header: Header::new(format, Timing::Metrical(u15::new(tpq.min(0x7FFF)))),
+            tracks: vec![vec![TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            }]],
+        }
+    }
+
     /// Parse a `.mid` Standard Midi File from its raw bytes.
     /// If you casually want to parse `.mid` files, this is the function you're looking for.
-    pub fn parse(raw: &[u8]) -> Result<Smf> {
+    pub fn parse(raw: &[u8]) -> Result<Smf<'_>> {
         let (header, tracks) = parse(raw)?;
         let track_count_hint = tracks.track_count_hint;
         let tracks = tracks.collect_tracks()?;
         validate_smf(&header, track_count_hint, tracks.len())?;
+        validate_sysex(&tracks)?;
         Ok(Smf { header, tracks })
     }
 
+    /// Parse several `.mid` Standard Midi Files concatenated one after the other in a single
+    /// buffer.
+    ///
+    /// Each file is returned along with the amount of bytes it takes up in the buffer.
+    /// Since files are not delimited in any other way, the track count declared in each header is
+    /// trusted to find out where the file ends: a file ends after its header chunk and as many
+    /// track chunks as declared (unknown chunks in between are included).
+    /// Parsing continues with the next file until the buffer is exhausted.
+    ///
+    /// RIFF-wrapped files are not supported.
+    pub fn parse_multi(mut raw: &[u8]) -> Result<Vec<(Smf<'_>, usize)>> {
+        let mut files = Vec::new();
+        while !raw.is_empty() {
+            let len = smf_len(raw)?;
+            let (file, rest) = raw.split_at(len);
+            files.push((Smf::parse(file)?, len));
+            raw = rest;
+        }
+        Ok(files)
+    }
+
+    /// Parse a `.mid` Standard Midi File from its raw bytes, enforcing the limits in the given
+    /// options.
+    ///
+    /// This is useful when parsing untrusted input, to bound the amount of memory and time spent
+    /// on a single file.
+    /// An error is returned as soon as any of the limits is exceeded.
+    /// See [`ParseOptions`](struct.ParseOptions.html) for the available limits.
+    pub fn parse_with_options<'r>(raw: &'r [u8], options: &ParseOptions) -> Result<Smf<'r>> {
+        let (header, tracks) = parse(raw)?;
+        let track_count_hint = tracks.track_count_hint;
+        let mut event_budget = options.max_total_events;
+        let tracks = tracks
+            .map(|events| {
+                let events = events?;
+                ensure!(
+                    events.unread().len() <= options.max_track_len,
+                    err_invalid!("track exceeds the maximum length")
+                );
+                let mut track =
+                    Vec::with_capacity(events.inner.estimate_events().min(event_budget));
+                for ev in events {
+                    event_budget = event_budget
+                        .checked_sub(1)
+                        .ok_or(err_invalid!("file exceeds the maximum amount of events"))?;
+                    track.push(ev?);
+                }
+                Ok(track)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        validate_smf(&header, track_count_hint, tracks.len())?;
+        validate_sysex(&tracks)?;
+        Ok(Smf { header, tracks })
+    }
+
+    /// Parse a `.mid` Standard Midi File, additionally reporting suspicious constructs that are
+    /// otherwise readable.
+    ///
+    /// Currently, the following issues are detected:
+    ///
+    /// - Notes that are turned on while already playing.
+    /// - Notes that are turned off while not playing.
+    /// - Notes that are never turned off (stuck notes).
+    /// - SysEx messages that lack a terminating `0xF7` byte.
+    /// - Variable-length integers encoded with redundant leading bytes.
+    ///
+    /// Note that unterminated SysEx messages are rejected with an error when the `strict` feature
+    /// is enabled.
+    ///
+    /// See [`WarningKind`](enum.WarningKind.html) for details.
+    /// Warnings are sorted by track, and then by event within each track.
+    pub fn parse_with_warnings(raw: &[u8]) -> Result<(Smf<'_>, Vec<Warning>)> {
+        let smf = Smf::parse(raw)?;
+        let (_, mut raw_tracks) = parse(raw)?;
+        let mut warnings = Vec::new();
+        for (idx, track) in smf.tracks.iter().enumerate() {
+            let first = warnings.len();
+            warning::check_notes(idx, track, &mut warnings);
+            warning::check_sysex(idx, track, &mut warnings);
+            //Encoding details are lost once parsed, so go through the raw track bytes again
+            if let Some(Ok(events)) = raw_tracks.next() {
+                warning::check_varlen(idx, events.bytemapped(), &mut warnings);
+            }
+            warnings[first..].sort_by_key(|warning| warning.event);
+        }
+        Ok((smf, warnings))
+    }
+
+    /// Parse a `.mid` Standard Midi File, additionally collecting any chunks that are neither
+    /// header nor track chunks, such as vendor-specific chunks.
+    ///
+    /// These chunks are usually skipped when parsing, but they can be written back at their
+    /// original position with [`write_with_chunks`](#method.write_with_chunks), allowing
+    /// non-destructive edits of files that contain them.
+    /// See [`UnknownChunk`](struct.UnknownChunk.html) for more information.
+    pub fn parse_with_chunks(raw: &[u8]) -> Result<(Smf<'_>, Vec<UnknownChunk<'_>>)> {
+        let smf = Smf::parse(raw)?;
+        let chunks = unknown_chunks(raw)?;
+        Ok((smf, chunks))
+    }
+
+    /// Parse a `.mid` Standard Midi File, stopping each track at its first `EndOfTrack` event and
+    /// collecting whatever bytes follow it within the track chunk.
+    ///
+    /// Some programs leave garbage bytes after the `EndOfTrack` event, but still within the
+    /// declared length of the track chunk.
+    /// Usually these bytes are parsed as if they were more events (or silently discarded if they
+    /// cannot be parsed), but with this method they are returned untouched instead, one slice per
+    /// track (empty for well-formed tracks).
+    /// They can then be written back with [`write_with_trailing`](#method.write_with_trailing),
+    /// making it possible to rewrite such files byte-for-byte.
+    ///
+    /// If the `strict` feature is enabled, any bytes after an `EndOfTrack` event are rejected with
+    /// an error instead.
+    pub fn parse_with_trailing(raw: &[u8]) -> Result<(Smf<'_>, Vec<&[u8]>)> {
+        let (header, tracks) = parse(raw)?;
+        let track_count_hint = tracks.track_count_hint;
+        let (tracks, trailing): (Vec<_>, Vec<_>) = tracks
+            .map(|events| {
+                let mut events = events?;
+                let mut track = Vec::with_capacity(events.inner.estimate_events());
+                for ev in events.by_ref() {
+                    let ev = ev?;
+                    track.push(ev);
+                    if ev.kind == TrackEventKind::Meta(MetaMessage::EndOfTrack) {
+                        break;
+                    }
+                }
+                if cfg!(feature = "strict") {
+                    ensure!(
+                        events.unread().is_empty(),
+                        err_malformed!("data after an end of track event")
+                    );
+                }
+                Ok((track, events.unread()))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        validate_smf(&header, track_count_hint, tracks.len())?;
+        validate_sysex(&tracks)?;
+        Ok((Smf { header, tracks }, trailing))
+    }
+
+    /// Parse a `.mid` Standard Midi File, keeping both the unknown chunks and the bytes after the
+    /// `EndOfTrack` event of each track.
+    ///
+    /// This combines [`parse_with_chunks`](#method.parse_with_chunks) and
+    /// [`parse_with_trailing`](#method.parse_with_trailing), so that files with both can be
+    /// rewritten byte-for-byte with [`write_preserving`](#method.write_preserving).
+    pub fn parse_preserving(raw: &[u8]) -> Result<(Smf<'_>, Preserved<'_>)> {
+        let (smf, trailing) = Smf::parse_with_trailing(raw)?;
+        let chunks = unknown_chunks(raw)?;
+        Ok((smf, Preserved { chunks, trailing }))
+    }
+
+    /// Borrow the list of tracks in this file.
+    ///
+    /// This is equivalent to borrowing the `tracks` field, and is provided for convenience when
+    /// iterating over the tracks without taking ownership of them.
+    #[inline]
+    pub fn tracks(&self) -> &[Track<'a>] {
+        &self.tracks
+    }
+
+    /// Borrow the events of the track at the given index, or `None` if there is no such track.
+    #[inline]
+    pub fn track(&self, idx: usize) -> Option<&[TrackEvent<'a>]> {
+        self.tracks.get(idx).map(|track| &track[..])
+    }
+
+    /// Remove the track at the given index, shifting all tracks after it one position earlier.
+    ///
+    /// Returns the removed track, or `None` if there is no track at the given index.
+    /// The header needs no update, since the amount of tracks is only computed when writing.
+    #[inline]
+    pub fn remove_track(&mut self, idx: usize) -> Option<Track<'a>> {
+        if idx < self.tracks.len() {
+            Some(self.tracks.remove(idx))
+        } else {
+            None
+        }
+    }
+
+    /// Insert a track at the given index, shifting all tracks after it one position later.
+    ///
+    /// A `SingleTrack` file can only hold one track, so inserting a track into a `SingleTrack`
+    /// file that already has a track fails.
+    /// To add tracks to such a file, convert it to a `Parallel` file first by changing
+    /// `header.format`, which is always valid for a file with a single track.
+    ///
+    /// Fails if `idx` is past the end of the track list, or if the file would end up with more
+    /// than 65535 tracks, which cannot be written.
+    pub fn insert_track(&mut self, idx: usize, track: Track<'a>) -> Result<()> {
+        ensure!(
+            idx <= self.tracks.len(),
+            err_invalid!("track index out of range")
+        );
+        ensure!(
+            self.header.format != Format::SingleTrack || self.tracks.is_empty(),
+            err_invalid!("singletrack format file cannot have multiple tracks")
+        );
+        ensure!(
+            self.tracks.len() < u16::MAX as usize,
+            err_invalid!("too many tracks")
+        );
+        self.tracks.insert(idx, track);
+        Ok(())
+    }
+
+    /// Build a standalone `SingleTrack` file out of the track at the given index, with the same
+    /// timing as this file.
+    ///
+    /// If this file has a [conductor track](#method.conductor_track) other than the exported
+    /// track, its `Tempo`, `TimeSignature`, `KeySignature` and `SmpteOffset` events are merged
+    /// into the exported track, since they apply to every track.
+    /// If the resulting track still has no `Tempo` or `TimeSignature` events, the defaults of 120
+    /// beats per minute and 4/4 time are inserted at its start, so that the exported file is
+    /// self-contained.
+    ///
+    /// Returns `None` if there is no track at the given index.
+    pub fn export_track(&self, idx: usize) -> Option<Smf<'a>> {
+        let track = self.tracks.get(idx)?;
+        let mut track = match self.conductor_track() {
+            Some(conductor) if idx != 0 => {
+                let mut global = conductor.clone();
+                crate::edit::retain(&mut global, |ev| {
+                    matches!(
+                        ev.kind,
+                        TrackEventKind::Meta(
+                            MetaMessage::Tempo(_)
+                                | MetaMessage::TimeSignature(..)
+                                | MetaMessage::KeySignature(..)
+                                | MetaMessage::SmpteOffset(_)
+                        )
+                    )
+                });
+                crate::edit::merge(&[global, track.clone()])
+            }
+            _ => track.clone(),
+        };
+        let has = |track: &[TrackEvent], f: fn(&MetaMessage) -> bool| {
+            track
+                .iter()
+                .any(|ev| matches!(&ev.kind, TrackEventKind::Meta(meta) if f(meta)))
+        };
+        if !has(&track, |meta| {
+            matches!(meta, MetaMessage::TimeSignature(..))
+        }) {
+            track.insert(
+                0,
+                TrackEventKind::Meta(MetaMessage::TimeSignature(4, 2, 24, 8)).into(),
+            );
+        }
+        if !has(&track, |meta| matches!(meta, MetaMessage::Tempo(_))) {
+            track.insert(
+                0,
+                TrackEventKind::Meta(MetaMessage::Tempo(u24::new(500_000))).into(),
+            );
+        }
+        Some(Smf {
+            header: Header::new(Format::SingleTrack, self.header.timing),
+            tracks: vec![track],
+        })
+    }
+
+    /// Get the effective sequence number of the track at the given index.
+    ///
+    /// Sequence numbers identify the patterns of `Format::Sequential` files.
+    /// If the track starts with a `TrackNumber` meta event carrying a number, that number is used.
+    /// Otherwise, including when the `TrackNumber` event is empty, the sequence number defaults
+    /// to the index of the track.
+    ///
+    /// Returns `None` if there is no track at the given index.
+    pub fn track_number(&self, idx: usize) -> Option<u16> {
+        let track = self.tracks.get(idx)?;
+        let explicit = track
+            .iter()
+            .take_while(|ev| ev.delta == 0)
+            .find_map(|ev| match ev.kind {
+                TrackEventKind::Meta(MetaMessage::TrackNumber(num)) => Some(num),
+                _ => None,
+            })
+            .flatten();
+        explicit.or_else(|| u16::try_from(idx).ok())
+    }
+
+    /// Iterate over the events of all tracks, each one tagged with the index of its track and its
+    /// absolute tick within the track.
+    ///
+    /// Events are yielded track by track, each track in order.
+    /// To iterate over the events of all tracks in playback order instead, see
+    /// [`edit::merge`](edit/fn.merge.html).
+    pub fn iter_all(&self) -> impl Iterator<Item = (usize, u64, &TrackEvent<'a>)> + '_ {
+        self.tracks.iter().enumerate().flat_map(|(idx, track)| {
+            track.iter().scan(0, move |tick, ev| {
+                *tick += ev.delta.as_int() as u64;
+                Some((idx, *tick, ev))
+            })
+        })
+    }
+
+    /// Apply a function to every event of every track, in order.
+    ///
+    /// Note that changing the delta time of an event shifts all of the events after it in the
+    /// same track.
+    pub fn map_events(&mut self, mut f: impl FnMut(&mut TrackEvent<'a>)) {
+        for ev in self.tracks.iter_mut().flatten() {
+            f(ev);
+        }
+    }
+
+    /// Replace or remove every event of every track, preserving the timing of the remaining
+    /// events.
+    ///
+    /// The function receives the kind of each event, and returns the kind of the event to put in
+    /// its place, or `None` to remove it.
+    /// The delta time of every removed event is folded into the next event, in the same way as
+    /// [`edit::retain`](edit/fn.retain.html).
+    pub fn filter_map_events(
+        &mut self,
+        mut f: impl FnMut(TrackEventKind<'a>) -> Option<TrackEventKind<'a>>,
+    ) {
+        for track in self.tracks.iter_mut() {
+            let mapped = track.iter().map(|ev| f(ev.kind)).collect::<Vec<_>>();
+            for (ev, kind) in track.iter_mut().zip(mapped.iter()) {
+                if let Some(kind) = *kind {
+                    ev.kind = kind;
+                }
+            }
+            let mut keep = mapped.iter().map(Option::is_some);
+            crate::edit::retain(track, |_| keep.next().unwrap_or(true));
+        }
+    }
+
     /// Encodes and writes the file to the given generic writer.
     ///
     /// Note that this function requires a `midly::io::Write` writer, not a `std::io::Write` writer.
@@ -100,6 +476,95 @@ impl<'a> Smf<'a> {
         write(&self.header, &self.tracks, out)
     }
 
+    /// Encodes and writes the file to the given generic writer, using custom write options.
+    ///
+    /// See [`WriteOptions`](struct.WriteOptions.html) for the available options.
+    #[inline]
+    pub fn write_with_options<W: Write>(
+        &self,
+        options: &WriteOptions,
+        out: &mut W,
+    ) -> WriteResult<W> {
+        write_with_options(&self.header, &self.tracks, options, out)
+    }
+
+    /// Encodes and writes the file to the given generic writer, along with additional chunks.
+    ///
+    /// Each chunk is written right before the track at its
+    /// [`position`](struct.UnknownChunk.html#structfield.position), or after the last track if its
+    /// position is past the end.
+    /// Chunks with the same position are written in the order they are given.
+    /// Together with [`parse_with_chunks`](#method.parse_with_chunks), this preserves unknown
+    /// chunks when rewriting a file.
+    ///
+    /// Tracks are always encoded with the default [`WriteOptions`](struct.WriteOptions.html).
+    /// Use [`write_preserving`](#method.write_preserving) to pick other options.
+    #[inline]
+    pub fn write_with_chunks<W: Write>(
+        &self,
+        chunks: &[UnknownChunk],
+        out: &mut W,
+    ) -> WriteResult<W> {
+        write_preserving(self, chunks, &[], &WriteOptions::default(), out)
+    }
+
+    /// Encodes and writes the file to the given generic writer, appending raw bytes to the end
+    /// of each track chunk.
+    ///
+    /// `trailing` holds the bytes to append to each track, in track order, and is usually
+    /// obtained from [`parse_with_trailing`](#method.parse_with_trailing).
+    /// Tracks past the end of `trailing` get no extra bytes.
+    /// The bytes are written verbatim after the last event, and are included in the length of the
+    /// track chunk.
+    ///
+    /// Tracks are always encoded with the default [`WriteOptions`](struct.WriteOptions.html).
+    /// Use [`write_preserving`](#method.write_preserving) to pick other options.
+    #[inline]
+    pub fn write_with_trailing<W: Write>(&self, trailing: &[&[u8]], out: &mut W) -> WriteResult<W> {
+        write_preserving(self, &[], trailing, &WriteOptions::default(), out)
+    }
+
+    /// Encodes and writes the file to the given generic writer, along with the unknown chunks and
+    /// trailing track bytes of a [`Preserved`](struct.Preserved.html) value.
+    ///
+    /// Chunks are placed as in [`write_with_chunks`](#method.write_with_chunks), and trailing
+    /// bytes are appended as in [`write_with_trailing`](#method.write_with_trailing).
+    /// Together with [`parse_preserving`](#method.parse_preserving), this rewrites a file
+    /// byte-for-byte, as long as the tracks are encoded the same way they were in the original file.
+    #[inline]
+    pub fn write_preserving<W: Write>(
+        &self,
+        preserved: &Preserved,
+        options: &WriteOptions,
+        out: &mut W,
+    ) -> WriteResult<W> {
+        write_preserving(self, &preserved.chunks, &preserved.trailing, options, out)
+    }
+
+    /// Compute the exact amount of bytes that [`write`](#method.write) produces, without
+    /// actually encoding the file into a buffer.
+    ///
+    /// This is useful to preallocate buffers or to announce the length of the file in advance.
+    /// If the file cannot be written at all (for example, because it has too many tracks) the
+    /// result is meaningless.
+    #[inline]
+    pub fn encoded_len(&self) -> usize {
+        self.encoded_len_with_options(&WriteOptions::default())
+    }
+
+    /// Compute the exact amount of bytes that
+    /// [`write_with_options`](#method.write_with_options) produces with the given options.
+    ///
+    /// See [`encoded_len`](#method.encoded_len) for more information.
+    pub fn encoded_len_with_options(&self, options: &WriteOptions) -> usize {
+        //The header chunk has a fixed size, and each track chunk has an 8-byte header
+        let mut counter = WriteCounter(14 + 8 * self.tracks.len() as u64);
+        for track in self.tracks.iter() {
+            let _ = Chunk::write_raw(track.iter(), options, &mut counter);
+        }
+        counter.0 as usize
+    }
+
     /// Encodes and writes the file to the given `std::io::Write` writer.
     ///
     /// This function is similar to the [`write`](#method.write) method, but writes to a
@@ -186,11 +651,12 @@ impl<'a> SmfBytemap<'a> {
 
     /// Parse a Standard Midi File from its raw bytes, keeping a map to the original bytes that
     /// make up each event.
-    pub fn parse(raw: &[u8]) -> Result<SmfBytemap> {
+    pub fn parse(raw: &[u8]) -> Result<SmfBytemap<'_>> {
         let (header, tracks) = parse(raw)?;
         let track_count_hint = tracks.track_count_hint;
         let tracks = tracks.collect_bytemapped()?;
         validate_smf(&header, track_count_hint, tracks.len())?;
+        validate_sysex(tracks.iter().map(|track| track.iter().map(|(_, ev)| ev)))?;
         Ok(SmfBytemap { header, tracks })
     }
 
@@ -253,13 +719,56 @@ fn validate_smf(header: &Header, track_count_hint: u16, track_count: usize) -> R
     Ok(())
 }
 
+#[cfg(feature = "alloc")]
+fn validate_sysex<'a, 'b: 'a, T, E>(tracks: T) -> Result<()>
+where
+    T: IntoIterator<Item = E>,
+    E: IntoIterator<Item = &'a TrackEvent<'b>>,
+{
+    if cfg!(feature = "strict") {
+        let mut warnings = Vec::new();
+        for track in tracks {
+            warning::check_sysex(0, track, &mut warnings);
+            ensure!(
+                warnings.is_empty(),
+                err_malformed!("sysex message is never terminated")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Find out the length of the file at the start of the given buffer, according to the track
+/// count declared in its header.
+#[cfg(feature = "alloc")]
+fn smf_len(raw: &[u8]) -> Result<usize> {
+    ensure!(raw.starts_with(b"MThd"), err_invalid!("not a midi file"));
+    let mut rest = raw;
+    let mut tracks_left = None;
+    while tracks_left != Some(0) && !rest.is_empty() {
+        let id = rest
+            .split_checked(4)
+            .ok_or(err_invalid!("failed to read chunkid"))?;
+        let len = u32::read(&mut rest).context(err_invalid!("failed to read chunklen"))?;
+        let chunk = rest
+            .split_checked(len as usize)
+            .unwrap_or_else(|| mem::take(&mut rest));
+        match (id, &mut tracks_left) {
+            (b"MThd", None) => tracks_left = Some(Header::read(chunk)?.1),
+            (b"MTrk", Some(left)) => *left -= 1,
+            _ => {}
+        }
+    }
+    Ok(raw.len() - rest.len())
+}
+
 /// Parse a raw MIDI file lazily, yielding its header and a lazy track iterator.
 /// No allocations are made.
 ///
 /// The track iterator that is returned yields event iterators, which in turn yield concrete events.
 ///
 /// This function is always available, even in `no_std` environments.
-pub fn parse(raw: &[u8]) -> Result<(Header, TrackIter)> {
+pub fn parse(raw: &[u8]) -> Result<(Header, TrackIter<'_>)> {
     let raw = match raw.get(..4) {
         Some(b"RIFF") => riff::unwrap(raw)?,
         Some(b"MThd") => raw,
@@ -277,6 +786,73 @@ pub fn parse(raw: &[u8]) -> Result<(Header, TrackIter)> {
     Ok((header, tracks))
 }
 
+/// Check whether a MIDI file survives being parsed and written back unchanged.
+///
+/// The file is parsed and then re-encoded, and the result is compared byte-by-byte to the
+/// original file.
+/// The only difference that is tolerated is the use of running status, since the writer always
+/// uses running status where possible.
+/// Any other difference, such as unknown chunks, a RIFF wrapper, non-minimal variable-length
+/// integers or data after the end of a track, makes this function return `false`.
+///
+/// This is useful to verify that `midly` understands every byte of a file, or to test tools that
+/// edit files with `midly`.
+/// Errors are only returned if the file cannot be parsed at all.
+///
+/// This function is only available with the `alloc` feature enabled.
+#[cfg(feature = "alloc")]
+pub fn roundtrip_equal(raw: &[u8]) -> Result<bool> {
+    /// The length of a file if no MIDI event used running status, along with its events.
+    fn normalize(raw: &[u8]) -> Result<(usize, SmfBytemap<'_>)> {
+        let smf = SmfBytemap::parse(raw)?;
+        let omitted = smf
+            .tracks
+            .iter()
+            .flatten()
+            .filter(|(bytes, ev)| {
+                matches!(ev.kind, TrackEventKind::Midi { .. }) && bytes.first() < Some(&0x80)
+            })
+            .count();
+        Ok((raw.len() + omitted, smf))
+    }
+    /// Strip the (possibly omitted) status byte off MIDI events.
+    fn data<'a>(bytes: &'a [u8], ev: &TrackEvent) -> &'a [u8] {
+        match (ev.kind, bytes.split_first()) {
+            (TrackEventKind::Midi { .. }, Some((&status, data))) if status >= 0x80 => data,
+            _ => bytes,
+        }
+    }
+
+    let (raw_len, original) = normalize(raw)?;
+    let mut rewritten = Vec::with_capacity(raw.len());
+    let options = WriteOptions {
+        ensure_end_of_track: false,
+        ..WriteOptions::default()
+    };
+    let tracks = original
+        .tracks
+        .iter()
+        .map(|track| track.iter().map(|(_, ev)| ev));
+    if write_with_options(&original.header, tracks, &options, &mut rewritten).is_err() {
+        //The file cannot even be written back
+        return Ok(false);
+    }
+    let (rewritten_len, reparsed) = normalize(&rewritten)?;
+    Ok(raw_len == rewritten_len
+        && original.header == reparsed.header
+        && original.tracks.len() == reparsed.tracks.len()
+        && original
+            .tracks
+            .iter()
+            .zip(reparsed.tracks.iter())
+            .all(|(a, b)| {
+                a.len() == b.len()
+                    && a.iter().zip(b.iter()).all(|((a_bytes, a), (b_bytes, b))| {
+                        a == b && data(a_bytes, a) == data(b_bytes, b)
+                    })
+            }))
+}
+
 /// Encode and write a generic MIDI file into the given generic writer.
 /// The MIDI file is represented by a header and a list of tracks.
 ///
@@ -290,17 +866,45 @@ pub fn parse(raw: &[u8]) -> Result<(Header, TrackIter)> {
 ///
 /// # Implementation notes
 ///
-/// Currently this function will attempt to use multiple threads to encode the file if possible and
-/// the file is large enough to make it worth it.
+/// If the writer is seekable, events will be streamed directly into the writer, which will be
+/// seeked back in order to write down the chunk sizes.
+/// No memory is allocated in this case, no matter how long the tracks are.
+/// To write a `std::io::Write + std::io::Seek` writer this way, wrap it in a
+/// [`SeekableWrap`](io/struct.SeekableWrap.html) (ideally around a buffered writer).
+///
+/// Otherwise, this function will attempt to use multiple threads to encode the file if possible
+/// and the file is large enough to make it worth it.
 ///
 /// Otherwise, each track will be written to an in-memory buffer before writing to disk.
 ///
-/// If allocation is disabled, but the writer is seekable, the file will be written once and it
-/// will be seeked back in order to write down the chunk sizes.
+/// If allocation is disabled too, encoding will happen twice: once to determine the size of the
+/// chunks and once again to actually write down the file.
 ///
-/// Otherwise, encoding will happen twice: once to determine the size of the chunks and once again
-/// to actually write down the file.
+/// Tracks that do not end with an `EndOfTrack` meta event are terminated automatically.
+/// Use [`write_with_options`](fn.write_with_options.html) to disable this behaviour.
+#[inline]
 pub fn write<'a, T, E, W>(header: &Header, tracks: T, out: &mut W) -> WriteResult<W>
+where
+    T: IntoIterator<Item = E>,
+    T::IntoIter: ExactSizeIterator + Clone + Send,
+    E: IntoIterator<Item = &'a TrackEvent<'a>>,
+    E::IntoIter: Clone + Send,
+    W: Write,
+{
+    write_with_options(header, tracks, &WriteOptions::default(), out)
+}
+
+/// Encode and write a generic MIDI file into the given generic writer, using custom write
+/// options.
+///
+/// See [`write`](fn.write.html) for more information, and
+/// [`WriteOptions`](struct.WriteOptions.html) for the available options.
+pub fn write_with_options<'a, T, E, W>(
+    header: &Header,
+    tracks: T,
+    options: &WriteOptions,
+    out: &mut W,
+) -> WriteResult<W>
 where
     T: IntoIterator<Item = E>,
     T::IntoIter: ExactSizeIterator + Clone + Send,
@@ -312,6 +916,14 @@ where
     //Write the header first
     Chunk::write_header(header, tracks.len(), out)?;
 
+    if let Some(out) = out.make_seekable() {
+        //Write down using seeks if the writer is seekable, avoiding any buffering
+        for track in tracks {
+            Chunk::write_seek(track, options, out)?;
+        }
+        return Ok(());
+    }
+
     //Try to write the file in parallel
     #[cfg(feature = "parallel")]
     {
@@ -330,7 +942,7 @@ where
                 .into_par_iter()
                 .map(|track| {
                     let mut track_chunk = Vec::new();
-                    Chunk::write_to_vec(track, &mut track_chunk)?;
+                    Chunk::write_to_vec(track, options, &mut track_chunk)?;
                     Ok(track_chunk)
                 })
                 .collect_into_vec(&mut track_chunks);
@@ -349,7 +961,7 @@ where
         //Write the tracks into a buffer before writing out to the file
         let mut buf = Vec::new();
         for track in tracks {
-            Chunk::write_to_vec(track, &mut buf).map_err(|msg| W::invalid_input(msg))?;
+            Chunk::write_to_vec(track, options, &mut buf).map_err(|msg| W::invalid_input(msg))?;
             out.write(&buf)?;
         }
         return Ok(());
@@ -357,19 +969,11 @@ where
 
     #[allow(unreachable_code)]
     {
-        if let Some(out) = out.make_seekable() {
-            //Write down using seeks if the writer is seekable
-            for track in tracks {
-                Chunk::write_seek(track, out)?;
-            }
-            return Ok(());
-        }
-
         //Last resort: do probe-writing.
         //Two passes are done: one to find out the size of the chunk and another to actually
         //write the chunk.
         for track in tracks {
-            Chunk::write_probe(track, out)?;
+            Chunk::write_probe(track, options, out)?;
         }
         Ok(())
     }
@@ -392,6 +996,248 @@ where
     write(header, tracks, &mut IoWrap(out))
 }
 
+/// Limits that are enforced while parsing MIDI files.
+///
+/// Used by [`Smf::parse_with_options`](struct.Smf.html#method.parse_with_options).
+/// The default limits are generous enough for any reasonable MIDI file, but still bound the
+/// resources used by maliciously crafted files.
+///
+/// More options may be added in the future, so this type can only be built through its `Default`
+/// implementation, changing the fields afterwards:
+///
+/// ```rust
+/// let mut options = midly::ParseOptions::default();
+/// options.max_total_events = 1_000_000;
+/// # let _ = options;
+/// ```
+///
+/// This type is only available with the `alloc` feature enabled.
+#[cfg(feature = "alloc")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[non_exhaustive]
+pub struct ParseOptions {
+    /// The maximum length of a single track chunk, in bytes.
+    ///
+    /// By default, 64MB.
+    pub max_track_len: usize,
+    /// The maximum amount of events in the entire file, across all tracks.
+    ///
+    /// By default, 16 million events.
+    pub max_total_events: usize,
+}
+#[cfg(feature = "alloc")]
+impl Default for ParseOptions {
+    #[inline]
+    fn default() -> ParseOptions {
+        ParseOptions {
+            max_track_len: 64 * 1024 * 1024,
+            max_total_events: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// A chunk of a MIDI file that is neither a header chunk (`MThd`) nor a track chunk (`MTrk`).
+///
+/// Such chunks are skipped when parsing, as the specification requires, but some programs store
+/// proprietary data in them.
+/// They can be collected with [`Smf::parse_with_chunks`](struct.Smf.html#method.parse_with_chunks)
+/// and written back with [`Smf::write_with_chunks`](struct.Smf.html#method.write_with_chunks).
+///
+/// This type is only available with the `alloc` feature enabled.
+#[cfg(feature = "alloc")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct UnknownChunk<'a> {
+    /// The four-byte type identifier of the chunk.
+    pub tag: [u8; 4],
+    /// The contents of the chunk, excluding its type and length.
+    pub data: &'a [u8],
+    /// The amount of track chunks that precede this chunk in the file.
+    ///
+    /// For example, a position of `0` places the chunk right after the header chunk.
+    pub position: usize,
+}
+
+/// The parts of a MIDI file that are discarded by a regular parse, but are needed to rewrite it
+/// byte-for-byte.
+///
+/// Obtained from [`Smf::parse_preserving`](struct.Smf.html#method.parse_preserving) and written
+/// back with [`Smf::write_preserving`](struct.Smf.html#method.write_preserving).
+///
+/// This type is only available with the `alloc` feature enabled.
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq, Debug, Hash, Default)]
+pub struct Preserved<'a> {
+    /// The chunks that are neither header nor track chunks, in file order.
+    pub chunks: Vec<UnknownChunk<'a>>,
+    /// The bytes after the `EndOfTrack` event of each track, in track order.
+    pub trailing: Vec<&'a [u8]>,
+}
+
+/// Collect the chunks of a file that are neither header nor track chunks.
+#[cfg(feature = "alloc")]
+fn unknown_chunks(raw: &[u8]) -> Result<Vec<UnknownChunk<'_>>> {
+    let mut raw = match raw.get(..4) {
+        Some(b"RIFF") => riff::unwrap(raw)?,
+        _ => raw,
+    };
+    let mut chunks = Vec::new();
+    let mut position = 0;
+    while !raw.is_empty() {
+        let (id, len) = match raw.split_checked(4).zip(u32::read(&mut raw).ok()) {
+            Some(chunk_header) => chunk_header,
+            None => {
+                if cfg!(feature = "strict") {
+                    bail!(err_malformed!("invalid chunk"));
+                } else {
+                    //Ignore the unreadable remainder, just like `TrackIter` does
+                    break;
+                }
+            }
+        };
+        let data = raw
+            .split_checked(len as usize)
+            .unwrap_or_else(|| mem::take(&mut raw));
+        match id {
+            b"MThd" => {}
+            b"MTrk" => position += 1,
+            _ => chunks.push(UnknownChunk {
+                tag: [id[0], id[1], id[2], id[3]],
+                data,
+                position,
+            }),
+        }
+    }
+    Ok(chunks)
+}
+
+/// Write a file along with extra chunks and trailing track bytes.
+#[cfg(feature = "alloc")]
+fn write_preserving<W: Write>(
+    smf: &Smf,
+    chunks: &[UnknownChunk],
+    trailing: &[&[u8]],
+    options: &WriteOptions,
+    out: &mut W,
+) -> WriteResult<W> {
+    let track_count = smf.tracks.len();
+    Chunk::write_header(&smf.header, track_count, out)?;
+    let mut buf = Vec::new();
+    for idx in 0..=track_count {
+        for chunk in chunks
+            .iter()
+            .filter(|chunk| chunk.position == idx || (idx == track_count && chunk.position > idx))
+        {
+            out.write(&chunk.tag)?;
+            out.write(&Chunk::check_len::<W, _>(chunk.data.len())?)?;
+            out.write(chunk.data)?;
+        }
+        if let Some(track) = smf.tracks.get(idx) {
+            Chunk::write_to_vec(track.iter(), options, &mut buf)
+                .map_err(|msg| W::invalid_input(msg))?;
+            if let Some(extra) = trailing.get(idx) {
+                buf.extend_from_slice(extra);
+                let len = Chunk::check_len::<W, _>(buf.len() - 8)?;
+                buf[4..8].copy_from_slice(&len);
+            }
+            out.write(&buf)?;
+        }
+    }
+    Ok(())
+}
+
+/// Options that control how MIDI files are encoded.
+///
+/// Used by [`write_with_options`](fn.write_with_options.html) and
+/// [`Smf::write_with_options`](struct.Smf.html#method.write_with_options).
+///
+/// More options may be added in the future, so this type can only be built through its `Default`
+/// implementation, changing the fields afterwards:
+///
+/// ```rust
+/// let mut options = midly::WriteOptions::default();
+/// options.running_status = false;
+/// # let _ = options;
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[non_exhaustive]
+pub struct WriteOptions {
+    /// Whether to terminate tracks that do not end with an `EndOfTrack` meta event.
+    ///
+    /// If enabled, an `EndOfTrack` event with a delta time of 0 is written after the last event of
+    /// any track that lacks one.
+    /// Note that `EndOfTrack` events in the middle of a track are written as-is.
+    ///
+    /// Enabled by default.
+    pub ensure_end_of_track: bool,
+    /// The maximum delta time to write in a single event, for devices that cannot handle large
+    /// delta times.
+    ///
+    /// Events with a larger delta time are preceded by as many filler events as necessary, each
+    /// one with a delta time of at most `max_delta`.
+    /// The filler events are empty `Text` meta events (`FF 01 00`), which players ignore.
+    /// Note that meta events cancel running status, so the next MIDI event is written with an
+    /// explicit status byte.
+    /// A limit of zero is treated as a limit of one tick.
+    ///
+    /// `None` by default, which writes all delta times as-is.
+    pub max_delta: Option<u28>,
+    /// Whether to write `NoteOff` messages as `NoteOn` messages with a velocity of zero.
+    ///
+    /// Both encodings release the note, but since a track usually consists of long runs of
+    /// note-ons and note-offs on the same channel, encoding both as note-ons allows running status
+    /// to omit most status bytes, producing smaller files.
+    /// Many sequencers write files this way.
+    /// Note that the release velocity of the `NoteOff` messages is lost.
+    ///
+    /// Disabled by default.
+    pub note_off_as_note_on_zero: bool,
+    /// Whether to add the missing terminating `0xF7` byte to SysEx messages that lack one.
+    ///
+    /// Some devices produce SysEx dumps without the terminating byte, which are accepted when
+    /// parsing unless the `strict` feature is enabled.
+    /// If this option is enabled, such messages are written with the terminating byte, so that
+    /// they become well-formed.
+    /// A SysEx event immediately followed by an escape event is considered to be the first packet
+    /// of a message split into several packets, and is written as-is.
+    ///
+    /// Disabled by default, so that files are written back exactly as they were read.
+    pub terminate_sysex: bool,
+    /// Whether to omit the status byte of MIDI events that share the status of the previous MIDI
+    /// event, as allowed by running status.
+    ///
+    /// Disabling running status makes every event self-describing, which produces larger files
+    /// but a canonical representation that can be compared byte-by-byte.
+    /// Note that parsed events always carry their full status regardless of how they were
+    /// encoded (see [`TrackEventKind::status_byte`](enum.TrackEventKind.html#method.status_byte)).
+    ///
+    /// Enabled by default.
+    pub running_status: bool,
+    /// The maximum amount of bytes to write in a single SysEx packet, for devices with a small
+    /// input buffer.
+    ///
+    /// SysEx messages with more data bytes than this are split into an initial `0xF0` packet
+    /// followed by as many `0xF7` continuation packets (escapes) as necessary, each one with at
+    /// most `max_sysex_packet` data bytes and a delta time of 0.
+    /// The final packet includes the terminating `0xF7` byte.
+    /// A limit of zero is treated as a limit of one byte.
+    ///
+    /// `None` by default, which writes SysEx messages in a single packet.
+    pub max_sysex_packet: Option<usize>,
+}
+impl Default for WriteOptions {
+    #[inline]
+    fn default() -> WriteOptions {
+        WriteOptions {
+            ensure_end_of_track: true,
+            max_delta: None,
+            note_off_as_note_on_zero: false,
+            terminate_sysex: false,
+            running_status: true,
+            max_sysex_packet: None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct ChunkIter<'a> {
     /// Starts at the current index, ends at EOF.
@@ -399,11 +1245,12 @@ struct ChunkIter<'a> {
 }
 impl<'a> ChunkIter<'a> {
     #[inline]
-    fn new(raw: &'a [u8]) -> ChunkIter {
+    fn new(raw: &'a [u8]) -> ChunkIter<'a> {
         ChunkIter { raw }
     }
 
     #[inline]
+    #[allow(clippy::wrong_self_convention)]
     fn as_tracks(self, track_count_hint: u16) -> TrackIter<'a> {
         TrackIter {
             chunks: self,
@@ -455,7 +1302,7 @@ impl<'a> Chunk<'a> {
                         bail!(err_malformed!("reached eof before chunk ended"));
                     } else {
                         //Just use the remainder of the file
-                        mem::replace(raw, &[])
+                        mem::take(raw)
                     }
                 }
             };
@@ -492,15 +1339,16 @@ impl<'a> Chunk<'a> {
     /// to actually write the chunk contents.
     fn write_probe<W: Write>(
         track: impl Iterator<Item = &'a TrackEvent<'a>> + Clone,
+        options: &WriteOptions,
         out: &mut W,
     ) -> WriteResult<W> {
         let mut counter = WriteCounter(0);
-        Self::write_raw(track.clone(), &mut counter).map_err(W::invalid_input)?;
+        Self::write_raw(track.clone(), options, &mut counter).map_err(W::invalid_input)?;
         let len = Self::check_len::<W, _>(counter.0)?;
         let mut head = [b'M', b'T', b'r', b'k', 0, 0, 0, 0];
         head[4..8].copy_from_slice(&len);
         out.write(&head)?;
-        Self::write_raw(track, out)?;
+        Self::write_raw(track, options, out)?;
         Ok(())
     }
 
@@ -510,11 +1358,12 @@ impl<'a> Chunk<'a> {
     /// last.
     fn write_seek<W: Write + Seek>(
         track: impl Iterator<Item = &'a TrackEvent<'a>>,
+        options: &WriteOptions,
         out: &mut W,
     ) -> WriteResult<W> {
         out.write(b"MTrk\0\0\0\0")?;
         let start = out.tell()?;
-        Self::write_raw(track, out)?;
+        Self::write_raw(track, options, out)?;
         let len = Self::check_len::<W, _>(out.tell()? - start)?;
         out.write_at(&len, start - 4)?;
         Ok(())
@@ -527,13 +1376,14 @@ impl<'a> Chunk<'a> {
     #[cfg(feature = "alloc")]
     fn write_to_vec(
         track: impl Iterator<Item = &'a TrackEvent<'a>>,
+        options: &WriteOptions,
         out: &mut Vec<u8>,
     ) -> WriteResult<Vec<u8>> {
         let cap = (track.size_hint().0 as f32 * EVENTS_TO_BYTES) as usize;
         out.clear();
         out.reserve(8 + cap);
         out.extend_from_slice(b"MTrk\0\0\0\0");
-        Self::write_raw(track, out)?;
+        Self::write_raw(track, options, out)?;
         let len = Self::check_len::<Vec<u8>, _>(out.len() - 8)?;
         out[4..8].copy_from_slice(&len);
         Ok(())
@@ -542,11 +1392,97 @@ impl<'a> Chunk<'a> {
     /// Auxiliary method. Iterate over the events of a track and write them out.
     fn write_raw<W: Write>(
         track: impl Iterator<Item = &'a TrackEvent<'a>>,
+        options: &WriteOptions,
         out: &mut W,
     ) -> WriteResult<W> {
+        let mut track = track.peekable();
         let mut running_status = None;
-        for ev in track {
-            ev.write(&mut running_status, out)?;
+        let mut terminated = false;
+        while let Some(ev) = track.next() {
+            let mut ev = *ev;
+            if options.note_off_as_note_on_zero {
+                if let TrackEventKind::Midi { message, .. } = &mut ev.kind {
+                    if let MidiMessage::NoteOff { key, .. } = *message {
+                        *message = MidiMessage::NoteOn {
+                            key,
+                            vel: u7::new(0),
+                        };
+                    }
+                }
+            }
+            if !options.running_status {
+                running_status = None;
+            }
+            if let Some(max) = options.max_delta {
+                let max = max.max(u28::new(1));
+                while ev.delta > max {
+                    TrackEvent {
+                        delta: max,
+                        kind: TrackEventKind::Meta(MetaMessage::Text(&[])),
+                    }
+                    .write(&mut running_status, out)?;
+                    ev.delta = u28::new(ev.delta.as_int() - max.as_int());
+                }
+            }
+            match ev.kind {
+                TrackEventKind::SysEx(data) => {
+                    let terminate = options.terminate_sysex
+                        && data.last() != Some(&0xF7)
+                        && !matches!(
+                            track.peek().map(|next| next.kind),
+                            Some(TrackEventKind::Escape(_))
+                        );
+                    Self::write_sysex(ev.delta, data, terminate, options, out)?;
+                    running_status = None;
+                }
+                _ => ev.write(&mut running_status, out)?,
+            }
+            terminated = ev.kind == TrackEventKind::Meta(MetaMessage::EndOfTrack);
+        }
+        if options.ensure_end_of_track && !terminated {
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            }
+            .write(&mut running_status, out)?;
+        }
+        Ok(())
+    }
+
+    /// Auxiliary method. Write a SysEx event, optionally adding a terminating `0xF7` byte and
+    /// splitting it into packets according to the write options.
+    fn write_sysex<W: Write>(
+        delta: u28,
+        data: &[u8],
+        terminate: bool,
+        options: &WriteOptions,
+        out: &mut W,
+    ) -> WriteResult<W> {
+        //Write the message as if its data ended with `0xF7`, if it has to be terminated
+        let total = data.len() + terminate as usize;
+        let packet = options.max_sysex_packet.unwrap_or(total).max(1);
+        let mut start: usize = 0;
+        let mut delta = delta;
+        let mut status = 0xF0;
+        loop {
+            let end = total.min(start.saturating_add(packet));
+            let len = u32::try_from(end - start)
+                .ok()
+                .and_then(u28::try_from)
+                .ok_or_else(|| W::invalid_input("varlen slice exceeds 28 bits"))?;
+            delta.write_varlen(out)?;
+            out.write(&[status])?;
+            len.write_varlen(out)?;
+            out.write(&data[start.min(data.len())..end.min(data.len())])?;
+            if end > data.len() {
+                out.write(&[0xF7])?;
+            }
+            if end >= total {
+                break;
+            }
+            start = end;
+            delta = u28::new(0);
+            status = 0xF7;
         }
         Ok(())
     }
@@ -576,6 +1512,16 @@ pub struct Header {
     /// file might have changing tempos along the song.
     pub timing: Timing,
 }
+impl Default for Header {
+    /// A header for a `Format::Parallel` file with a resolution of 480 ticks per beat.
+    ///
+    /// 480 ticks per beat is a resolution common among sequencers, fine enough to represent any
+    /// reasonable rhythm.
+    #[inline]
+    fn default() -> Header {
+        Header::new(Format::Parallel, Timing::Metrical(u15::new(480)))
+    }
+}
 impl Header {
     /// Create a new header from its raw parts.
     #[inline]
@@ -583,6 +1529,21 @@ impl Header {
         Header { format, timing }
     }
 
+    /// Parse only the header of a raw MIDI file, without looking at any of its tracks.
+    ///
+    /// Returns the header along with the track count declared in it.
+    /// Note that the declared track count may not match the amount of track chunks actually
+    /// present in the file.
+    ///
+    /// This is much cheaper than parsing a whole file, which makes it useful to quickly scan large
+    /// amounts of files.
+    /// Like [`parse`](fn.parse.html), RIFF-wrapped files are also accepted.
+    #[inline]
+    pub fn parse(raw: &[u8]) -> Result<(Header, u16)> {
+        let (header, tracks) = parse(raw)?;
+        Ok((header, tracks.track_count_hint))
+    }
+
     /// Read the contents of a header chunk, including the `Header` and the track count.
     fn read(mut raw: &[u8]) -> Result<(Header, u16)> {
         let format = Format::read(&mut raw)?;
@@ -613,7 +1574,7 @@ impl<'a> TrackIter<'a> {
     ///
     /// The main way to obtain raw SMF without a header is the [`unread`](#method.unread) method.
     #[inline]
-    pub fn new(raw: &[u8]) -> TrackIter {
+    pub fn new(raw: &[u8]) -> TrackIter<'_> {
         TrackIter {
             chunks: ChunkIter::new(raw),
             track_count_hint: 0,
@@ -667,15 +1628,17 @@ impl<'a> TrackIter<'a> {
             .collect::<Result<Vec<Vec<T>>>>()
     }
 }
+//Once the file ends or an error occurs, the unread bytes are emptied and `None` is yielded forever
+impl<'a> FusedIterator for TrackIter<'a> {}
 impl<'a> Iterator for TrackIter<'a> {
     type Item = Result<EventIter<'a>>;
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (
-            self.track_count_hint as usize,
-            Some(self.track_count_hint as usize),
-        )
+        //The track count declared in the header cannot be trusted: some exporters declare zero
+        //tracks but still include several, and truncated files have less tracks than declared.
+        //Tracks are read until EOF regardless of the declared count.
+        (0, None)
     }
 
     #[inline]
@@ -712,20 +1675,42 @@ impl<'a> Iterator for TrackIter<'a> {
 trait EventKind<'a> {
     type Event: 'a;
     fn read_ev(raw: &mut &'a [u8], running_status: &mut Option<u8>) -> Result<Self::Event>;
+    fn is_end_of_track(ev: &Self::Event) -> bool;
+}
+
+/// The reason why an event iterator stopped yielding events.
+///
+/// Obtained through [`EventIter::finished_reason`](struct.EventIter.html#method.finished_reason).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum FinishReason {
+    /// The track data ran out right after an `EndOfTrack` meta event, as it should.
+    EndOfTrack,
+    /// The track data ran out, but the last event was not an `EndOfTrack` meta event.
+    ///
+    /// This usually means that the track was truncated.
+    Exhausted,
+    /// An event could not be parsed, and the rest of the track was discarded.
+    ///
+    /// Unless the `strict` feature is enabled, this happens silently, as if the track had ended.
+    Malformed,
 }
 
 #[derive(Clone, Debug)]
 struct EventIterGeneric<'a, T> {
     raw: &'a [u8],
     running_status: Option<u8>,
+    last_was_end: bool,
+    malformed: bool,
     _kind: PhantomData<T>,
 }
 impl<'a, T: EventKind<'a>> EventIterGeneric<'a, T> {
     #[inline]
-    fn new(raw: &[u8]) -> EventIterGeneric<T> {
+    fn new(raw: &[u8]) -> EventIterGeneric<'_, T> {
         EventIterGeneric {
             raw,
             running_status: None,
+            last_was_end: false,
+            malformed: false,
             _kind: PhantomData,
         }
     }
@@ -748,6 +1733,45 @@ impl<'a, T: EventKind<'a>> EventIterGeneric<'a, T> {
         &mut self.running_status
     }
 
+    /// Parse the next event without advancing the iterator.
+    #[inline]
+    fn peek(&self) -> Option<Result<T::Event>> {
+        EventIterGeneric::<T> {
+            raw: self.raw,
+            running_status: self.running_status,
+            last_was_end: self.last_was_end,
+            malformed: self.malformed,
+            _kind: PhantomData,
+        }
+        .next()
+    }
+
+    /// Get the reason why the iterator stopped, or `None` if there are events left.
+    #[inline]
+    fn finished_reason(&self) -> Option<FinishReason> {
+        if !self.raw.is_empty() {
+            None
+        } else if self.malformed {
+            Some(FinishReason::Malformed)
+        } else if self.last_was_end {
+            Some(FinishReason::EndOfTrack)
+        } else {
+            Some(FinishReason::Exhausted)
+        }
+    }
+
+    /// Convert into an iterator yielding a different kind of event, keeping the current state.
+    #[inline]
+    fn convert<U>(self) -> EventIterGeneric<'a, U> {
+        EventIterGeneric {
+            raw: self.raw,
+            running_status: self.running_status,
+            last_was_end: self.last_was_end,
+            malformed: self.malformed,
+            _kind: PhantomData,
+        }
+    }
+
     #[cfg(feature = "alloc")]
     #[inline]
     fn estimate_events(&self) -> usize {
@@ -777,13 +1801,30 @@ impl<'a, T: EventKind<'a>> EventIterGeneric<'a, T> {
 }
 impl<'a, T: EventKind<'a>> Iterator for EventIterGeneric<'a, T> {
     type Item = Result<T::Event>;
+
+    #[inline]
+    #[allow(clippy::manual_div_ceil)] //`usize::div_ceil` requires Rust 1.73
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        //The smallest possible event takes 2 bytes: a delta time and a data byte using running
+        //status. An error also counts as an item in strict mode, but consumes the rest of the
+        //track, so the last odd byte may yield one more item.
+        //The largest channel event takes 7 bytes: a 4-byte delta time, a status byte and 2 data
+        //bytes. Tracks are mostly made of channel events, so this gives a lower bound that is
+        //useful for preallocation, even though large SysEx or meta events (or invalid data,
+        //which silently ends the track) may produce fewer events.
+        (self.raw.len() / 7, Some((self.raw.len() + 1) / 2))
+    }
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         if !self.raw.is_empty() {
             match T::read_ev(&mut self.raw, &mut self.running_status) {
-                Ok(ev) => Some(Ok(ev)),
+                Ok(ev) => {
+                    self.last_was_end = T::is_end_of_track(&ev);
+                    Some(Ok(ev))
+                }
                 Err(err) => {
                     self.raw = &[];
+                    self.malformed = true;
                     if cfg!(feature = "strict") {
                         Some(Err(err).context(err_malformed!("malformed event")))
                     } else {
@@ -802,6 +1843,10 @@ impl<'a, T: EventKind<'a>> Iterator for EventIterGeneric<'a, T> {
 ///
 /// This iterator is lazy, it parses events as it goes, and therefore produces `Result<TrackEvent>>`
 /// rather than `TrackEvent`.
+/// The iterator stops after the first error, so it can be collected into a
+/// `Result<Vec<TrackEvent>>` that short-circuits on errors.
+/// Note that unless the `strict` feature is enabled, malformed events simply end the track
+/// silently, as if the track had ended.
 ///
 /// This type is always available, even in `no_std` environments.
 #[derive(Clone, Debug)]
@@ -814,6 +1859,10 @@ impl<'a> EventKind<'a> for EventIter<'a> {
     fn read_ev(raw: &mut &'a [u8], rs: &mut Option<u8>) -> Result<TrackEvent<'a>> {
         TrackEvent::read(raw, rs)
     }
+    #[inline]
+    fn is_end_of_track(ev: &TrackEvent<'a>) -> bool {
+        ev.kind == TrackEventKind::Meta(MetaMessage::EndOfTrack)
+    }
 }
 impl<'a> EventIter<'a> {
     /// Create an event iterator from raw track bytes.
@@ -822,7 +1871,7 @@ impl<'a> EventIter<'a> {
     /// Usually these raw track bytes are obtained from the [`unread`](#method.unread) method on an
     /// event iterator.
     #[inline]
-    pub fn new(raw: &[u8]) -> EventIter {
+    pub fn new(raw: &[u8]) -> EventIter<'_> {
         EventIter {
             inner: EventIterGeneric::new(raw),
         }
@@ -846,15 +1895,74 @@ impl<'a> EventIter<'a> {
         self.inner.running_status_mut()
     }
 
+    /// Look at the next event without consuming it, yielding exactly what the next call to
+    /// `next` would yield.
+    ///
+    /// This is useful to check whether the next event is due yet, for example.
+    /// Note that the event is parsed again when it is eventually consumed.
+    #[inline]
+    pub fn peek(&self) -> Option<Result<TrackEvent<'a>>> {
+        self.inner.peek()
+    }
+
+    /// Get the reason why this iterator stopped yielding events, or `None` if it has not stopped
+    /// yet.
+    ///
+    /// This distinguishes a properly terminated track, whose last event is an `EndOfTrack` meta
+    /// event, from a truncated or corrupted one, without having to look at the events again.
+    /// Note that the iterator does not stop at `EndOfTrack` events in the middle of a track, so
+    /// in that case the reason depends on whatever comes after them.
+    #[inline]
+    pub fn finished_reason(&self) -> Option<FinishReason> {
+        self.inner.finished_reason()
+    }
+
+    /// Check whether any of the remaining events is a `Controller` message for the given
+    /// controller number, such as `64` for the sustain pedal or `123` for "all notes off".
+    ///
+    /// This is much faster than checking the events one by one, since events are skipped over
+    /// without being decoded.
+    /// The iterator itself is not advanced.
+    ///
+    /// Corrupted track data is handled just like the iterator does: it is treated as the end of
+    /// the track, or if the `strict` feature is enabled, an error is returned if the track data is
+    /// corrupted before a matching controller is found.
+    pub fn contains_controller(&self, controller: u7) -> Result<bool> {
+        let mut raw = self.unread();
+        let mut running_status = self.running_status();
+        while !raw.is_empty() {
+            let ev = match RawEvent::read(&mut raw, &mut running_status) {
+                Ok(ev) => ev,
+                Err(err) => {
+                    if cfg!(feature = "strict") {
+                        return Err(err).context(err_malformed!("malformed event"));
+                    } else {
+                        //Ignore the rest of the track, just like the iterator does
+                        break;
+                    }
+                }
+            };
+            if ev.status >> 4 == 0xB && ev.data[0] == controller.as_int() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     /// Make this event iterator keep track of the raw bytes that make up each event.
     #[inline]
     pub fn bytemapped(self) -> EventBytemapIter<'a> {
         EventBytemapIter {
-            inner: EventIterGeneric {
-                raw: self.inner.raw,
-                running_status: self.inner.running_status,
-                _kind: PhantomData,
-            },
+            inner: self.inner.convert(),
+        }
+    }
+
+    /// Make this event iterator stop decoding events, yielding their raw status and data bytes
+    /// instead.
+    #[inline]
+    pub fn raw(self) -> RawEventIter<'a> {
+        RawEventIter {
+            inner: self.inner.convert(),
         }
     }
 
@@ -876,7 +1984,13 @@ impl<'a> Iterator for EventIter<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
+//Once the track ends or an error occurs, the unread bytes are emptied and `None` is yielded forever
+impl<'a> FusedIterator for EventIter<'a> {}
 
 /// An iterator over the events of a single track that keeps track of the raw bytes that make up
 /// each event.
@@ -896,6 +2010,10 @@ impl<'a> EventKind<'a> for EventBytemapIter<'a> {
     fn read_ev(raw: &mut &'a [u8], rs: &mut Option<u8>) -> Result<Self::Event> {
         TrackEvent::read_bytemap(raw, rs)
     }
+    #[inline]
+    fn is_end_of_track(ev: &Self::Event) -> bool {
+        EventIter::is_end_of_track(&ev.1)
+    }
 }
 impl<'a> EventBytemapIter<'a> {
     /// Create an event iterator from raw track bytes.
@@ -904,7 +2022,7 @@ impl<'a> EventBytemapIter<'a> {
     /// Usually these raw track bytes are obtained from the [`unread`](#method.unread) method on an
     /// event iterator.
     #[inline]
-    pub fn new(raw: &[u8]) -> EventBytemapIter {
+    pub fn new(raw: &[u8]) -> EventBytemapIter<'_> {
         EventBytemapIter {
             inner: EventIterGeneric::new(raw),
         }
@@ -928,15 +2046,33 @@ impl<'a> EventBytemapIter<'a> {
         self.inner.running_status_mut()
     }
 
+    /// Look at the next event without consuming it, yielding exactly what the next call to
+    /// `next` would yield.
+    ///
+    /// This is useful to check whether the next event is due yet, for example.
+    /// Note that the event is parsed again when it is eventually consumed.
+    #[inline]
+    pub fn peek(&self) -> Option<Result<(&'a [u8], TrackEvent<'a>)>> {
+        self.inner.peek()
+    }
+
+    /// Get the reason why this iterator stopped yielding events, or `None` if it has not stopped
+    /// yet.
+    ///
+    /// This distinguishes a properly terminated track, whose last event is an `EndOfTrack` meta
+    /// event, from a truncated or corrupted one, without having to look at the events again.
+    /// Note that the iterator does not stop at `EndOfTrack` events in the middle of a track, so
+    /// in that case the reason depends on whatever comes after them.
+    #[inline]
+    pub fn finished_reason(&self) -> Option<FinishReason> {
+        self.inner.finished_reason()
+    }
+
     /// Stop collecting bytemap information for any remaining events.
     #[inline]
     pub fn not_bytemapped(self) -> EventIter<'a> {
         EventIter {
-            inner: EventIterGeneric {
-                raw: self.inner.raw,
-                running_status: self.inner.running_status,
-                _kind: PhantomData,
-            },
+            inner: self.inner.convert(),
         }
     }
 
@@ -958,4 +2094,106 @@ impl<'a> Iterator for EventBytemapIter<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+//Once the track ends or an error occurs, the unread bytes are emptied and `None` is yielded forever
+impl<'a> FusedIterator for EventBytemapIter<'a> {}
+
+/// An iterator over the events of a single track that only finds the boundaries of each event,
+/// without decoding them.
+/// Created by the [`EventIter::raw`](struct.EventIter.html#method.raw) method.
+///
+/// Yields [`RawEvent`](struct.RawEvent.html)s, which hold the status byte and the data bytes of
+/// each event.
+/// Only running status and variable-length quantities are interpreted, so this iterator is
+/// faster than [`EventIter`](struct.EventIter.html), and loses none of the original data: the
+/// delta time, whether running status was used, and the bytes after the status byte are enough
+/// to re-emit each event verbatim, except for delta times encoded with redundant leading bytes.
+/// Otherwise it behaves exactly like `EventIter`, stopping after the first error.
+///
+/// This type is always available, even in `no_std` environments.
+#[derive(Clone, Debug)]
+pub struct RawEventIter<'a> {
+    inner: EventIterGeneric<'a, Self>,
+}
+impl<'a> EventKind<'a> for RawEventIter<'a> {
+    type Event = RawEvent<'a>;
+    #[inline]
+    fn read_ev(raw: &mut &'a [u8], rs: &mut Option<u8>) -> Result<RawEvent<'a>> {
+        RawEvent::read(raw, rs)
+    }
+    #[inline]
+    fn is_end_of_track(ev: &RawEvent<'a>) -> bool {
+        ev.status == 0xFF && ev.data.first() == Some(&0x2F)
+    }
+}
+impl<'a> RawEventIter<'a> {
+    /// Create a raw event iterator from raw track bytes.
+    #[inline]
+    pub fn new(raw: &[u8]) -> RawEventIter<'_> {
+        RawEventIter {
+            inner: EventIterGeneric::new(raw),
+        }
+    }
+
+    /// Get the remaining unparsed event bytes.
+    #[inline]
+    pub fn unread(&self) -> &'a [u8] {
+        self.inner.unread()
+    }
+
+    /// Get the current running status of the track.
+    #[inline]
+    pub fn running_status(&self) -> Option<u8> {
+        self.inner.running_status()
+    }
+
+    /// Look at the next event without consuming it, yielding exactly what the next call to
+    /// `next` would yield.
+    #[inline]
+    pub fn peek(&self) -> Option<Result<RawEvent<'a>>> {
+        self.inner.peek()
+    }
+
+    /// Get the reason why this iterator stopped yielding events, or `None` if it has not stopped
+    /// yet.
+    ///
+    /// See [`EventIter::finished_reason`](struct.EventIter.html#method.finished_reason).
+    #[inline]
+    pub fn finished_reason(&self) -> Option<FinishReason> {
+        self.inner.finished_reason()
+    }
+
+    /// Go back to fully decoding any remaining events.
+    #[inline]
+    pub fn decoded(self) -> EventIter<'a> {
+        EventIter {
+            inner: self.inner.convert(),
+        }
+    }
+
+    /// Collects the remaining unparsed events into a `Vec<RawEvent>`.
+    ///
+    /// This function is only available with the `alloc` feature enabled.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn into_vec(self) -> Result<Vec<RawEvent<'a>>> {
+        self.inner.into_vec()
+    }
+}
+impl<'a> Iterator for RawEventIter<'a> {
+    type Item = Result<RawEvent<'a>>;
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
+//Once the track ends or an error occurs, the unread bytes are emptied and `None` is yielded forever
+impl<'a> FusedIterator for RawEventIter<'a> {}
@@ -11,8 +11,8 @@
 
 use crate::{
     event::MidiMessage,
+    internal::*,
     live::{LiveEvent, SystemRealtime},
-    prelude::*,
 };
 
 /// A streaming raw MIDI parser.
@@ -23,6 +23,7 @@ use crate::{
 pub struct MidiStream<B = DefaultBuffer> {
     status: Option<u8>,
     data: B,
+    overflowed: bool,
 }
 impl MidiStream {
     /// Create a new clean midi stream with the default buffer size.
@@ -39,6 +40,7 @@ impl<B: Buffer> MidiStream<B> {
         MidiStream {
             status: None,
             data: buf,
+            overflowed: false,
         }
     }
 
@@ -75,6 +77,7 @@ impl<B: Buffer> MidiStream<B> {
                     Err(()) => {
                         //Data for this message is too long, drop it
                         self.status = None;
+                        self.overflowed = true;
                         self.data.clear();
                     }
                 }
@@ -116,6 +119,51 @@ impl<B: Buffer> MidiStream<B> {
         }
     }
 
+    /// Feed all of the bytes from a buffered reader to the stream until it is exhausted, calling the
+    /// `handle_ev` closure whenever a complete event is read.
+    ///
+    /// Bytes are fed straight out of the internal buffer of the reader, so no additional buffer is
+    /// needed.
+    /// Once the reader reaches end-of-file, the stream is [flushed](#method.flush).
+    /// Reads that fail with `ErrorKind::Interrupted` are retried, and any other error is returned
+    /// immediately, leaving the stream ready to continue where it left off.
+    ///
+    /// This function is only available with the `std` feature enabled.
+    #[cfg(feature = "std")]
+    pub fn feed_bufread<R: io::BufRead>(
+        &mut self,
+        mut reader: R,
+        mut handle_ev: impl FnMut(LiveEvent),
+    ) -> io::Result<()> {
+        loop {
+            let len = match reader.fill_buf() {
+                Ok([]) => break,
+                Ok(bytes) => {
+                    self.feed(bytes, &mut handle_ev);
+                    bytes.len()
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            reader.consume(len);
+        }
+        self.flush(handle_ev);
+        Ok(())
+    }
+
+    /// Check whether any message was dropped because it did not fit in the data buffer, and reset
+    /// the check.
+    ///
+    /// Only messages with unbounded length, such as SysEx messages, can overflow the buffer.
+    /// The remaining bytes of an overflowing message are discarded, and the stream recovers
+    /// cleanly at the next status byte.
+    /// This is mostly relevant for fixed-size buffers created with the
+    /// [`stack_buffer!`](../macro.stack_buffer.html) macro, in embedded environments.
+    #[inline]
+    pub fn take_overflow(&mut self) -> bool {
+        mem::replace(&mut self.overflowed, false)
+    }
+
     /// Indicates to the stream that this is an event boundary, such as for example when the stream
     /// is closed.
     /// Not calling this function might drop some pending events.
@@ -135,6 +183,7 @@ impl<B: Buffer> MidiStream<B> {
 /// macro, and very rarely should be implemented manually.
 pub trait Buffer {
     /// Add a new slice of data to the tail of the buffer.
+    #[allow(clippy::result_unit_err)]
     fn push(&mut self, data: &[u7]) -> StdResult<(), ()>;
     /// Clear the entire buffer.
     fn clear(&mut self);
@@ -51,7 +51,14 @@ fn test_rewrite(filename: &str) {
     println!("rewriting...");
     let mut file = Vec::with_capacity(16 * 1024);
     time(&format!("{} (rewrite)", filename), || {
-        smf.write(&mut file).expect("failed to rewrite midi file");
+        // Do not terminate tracks automatically, so that files lacking an `EndOfTrack` event
+        // are rewritten as-is
+        let options = crate::WriteOptions {
+            ensure_end_of_track: false,
+            ..crate::WriteOptions::default()
+        };
+        crate::write_with_options(&smf.header, smf.tracks.iter(), &options, &mut file)
+            .expect("failed to rewrite midi file");
     });
     println!("reparsing...");
     let clone_smf = time(&format!("{} (reparse)", filename), || {
@@ -75,7 +82,7 @@ mod parse_collect {
     }
     #[cfg(not(feature = "alloc"))]
     impl<'a> Smf<'a> {
-        pub fn parse(raw: &[u8]) -> MidlyResult<Smf> {
+        pub fn parse(raw: &[u8]) -> MidlyResult<Smf<'_>> {
             let (header, tracks) = crate::parse(raw)?;
             Ok(Smf {
                 header,
@@ -84,9 +91,6 @@ mod parse_collect {
                     .collect::<MidlyResult<Vec<_>>>()?,
             })
         }
-        pub fn write<W: crate::io::Write>(&self, out: &mut W) -> Result<(), W::Error> {
-            crate::write(&self.header, self.tracks.iter(), out)
-        }
     }
     pub fn len(_raw: &[u8], track: Vec<TrackEvent>) -> usize {
         track.len()
@@ -104,7 +108,7 @@ mod parse_bytemap {
     }
     #[cfg(not(feature = "alloc"))]
     impl<'a> Smf<'a> {
-        pub fn parse(raw: &[u8]) -> MidlyResult<Smf> {
+        pub fn parse(raw: &[u8]) -> MidlyResult<Smf<'_>> {
             let (header, tracks) = crate::parse(raw)?;
             Ok(Smf {
                 header,
@@ -121,7 +125,7 @@ mod parse_bytemap {
         //NOT consecutive (because delta times must interrupt every single event)
         for (bytes, _ev) in track.iter() {
             let mut advanced = false;
-            while !raw.starts_with(*bytes) {
+            while !raw.starts_with(bytes) {
                 advanced = true;
                 match raw.get(1..) {
                     Some(new_raw) => raw = new_raw,
@@ -137,11 +141,12 @@ mod parse_bytemap {
 mod parse_lazy {
     use super::*;
     pub struct Smf<'a> {
+        #[allow(dead_code)]
         pub header: crate::Header,
         pub tracks: crate::TrackIter<'a>,
     }
     impl Smf<'_> {
-        pub fn parse(raw: &[u8]) -> MidlyResult<Smf> {
+        pub fn parse(raw: &[u8]) -> MidlyResult<Smf<'_>> {
             let (header, tracks) = crate::parse(raw)?;
             Ok(Smf { header, tracks })
         }
@@ -273,6 +278,17 @@ fn live_system() {
         LiveEvent::parse(&[0xF2, 0x08, 0x01]).unwrap(),
         LiveEvent::Common(SystemCommon::SongPosition(136.into()))
     );
+    let pos = SystemCommon::SongPosition(136.into());
+    assert_eq!(pos.song_position_beats(), Some(136));
+    assert_eq!(
+        pos.song_position_ticks(crate::Timing::Metrical(96.into())),
+        Some(3264)
+    );
+    assert_eq!(
+        pos.song_position_ticks(crate::Timing::Timecode(crate::Fps::Fps25, 40)),
+        None
+    );
+    assert_eq!(SystemCommon::TuneRequest.song_position_beats(), None);
     assert_eq!(
         LiveEvent::parse(&[0xF3, 0x01]).unwrap(),
         LiveEvent::Common(SystemCommon::SongSelect(1.into()))
@@ -578,6 +594,81 @@ mod parse {
         assert_eq!(format!("{:?}", buf), format!("{:?}", buf_copy));
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn feed_bufread() {
+        use crate::{live::LiveEvent, stream::MidiStream};
+        use std::io::BufReader;
+
+        let bytes = [0x90, 60, 64, 62, 64, 0xF8, 0xC0, 5];
+        // A tiny buffer, so that events straddle refills
+        let reader = BufReader::with_capacity(3, &bytes[..]);
+        let mut events = Vec::new();
+        MidiStream::new()
+            .feed_bufread(reader, |ev| events.push(ev.to_static()))
+            .unwrap();
+        let mut expected = Vec::new();
+        let mut stream = MidiStream::new();
+        stream.feed(&bytes, |ev| expected.push(ev.to_static()));
+        stream.flush(|ev| expected.push(ev.to_static()));
+        assert_eq!(events, expected);
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[2], LiveEvent::Realtime(_)));
+    }
+
+    #[test]
+    fn realtime_within_running_status() {
+        use crate::{
+            live::{LiveEvent, SystemRealtime},
+            stream::MidiStream,
+            MidiMessage,
+        };
+
+        let note = |key: u8| LiveEvent::Midi {
+            channel: 0.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: 64.into(),
+            },
+        };
+        // A clock byte splits the data bytes of both the first and the running-status note-on
+        let bytes = [0x90, 0xF8, 60, 0xF8, 64, 62, 0xF8, 64];
+        let mut events = Vec::new();
+        let mut stream = MidiStream::new();
+        stream.feed(&bytes, |ev| events.push(ev.to_static()));
+        stream.flush(|ev| events.push(ev.to_static()));
+        let clock = LiveEvent::Realtime(SystemRealtime::TimingClock);
+        assert_eq!(events, [clock, clock, note(60), clock, note(62)]);
+    }
+
+    #[test]
+    fn stack_buf_overflow() {
+        use crate::{live::LiveEvent, stack_buffer, stream::MidiStream, MidiMessage};
+        stack_buffer! {
+            struct Buf([u8; 4]);
+        }
+        let mut stream = MidiStream::with_buffer(Buf::new());
+        let mut events = 0;
+        stream.feed(&[0xF0, 1, 2, 3], |_| events += 1);
+        assert!(!stream.take_overflow());
+        stream.feed(&[4, 5, 0xF7, 0x90, 60, 64], |ev| {
+            events += 1;
+            assert_eq!(
+                ev,
+                LiveEvent::Midi {
+                    channel: 0.into(),
+                    message: MidiMessage::NoteOn {
+                        key: 60.into(),
+                        vel: 64.into(),
+                    },
+                }
+            );
+        });
+        assert_eq!(events, 1);
+        assert!(stream.take_overflow());
+        assert!(!stream.take_overflow());
+    }
+
     #[cfg(feature = "alloc")]
     #[test]
     fn stable_arena() {
@@ -607,3 +698,3154 @@ mod parse {
         assert_send::<crate::Arena>();
     }
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn std_error_interop() {
+    use std::error::Error as StdError;
+
+    fn parse_boxed(raw: &[u8]) -> Result<(), Box<dyn StdError>> {
+        crate::parse(raw)?;
+        Ok(())
+    }
+    fn parse_io(raw: &[u8]) -> std::io::Result<()> {
+        crate::parse(raw)?;
+        Ok(())
+    }
+
+    // Truncated header chunk
+    let raw = b"MThd\0\0\0\x02\0\0";
+    let err = parse_boxed(raw).unwrap_err();
+    assert_eq!(err.to_string(), "invalid midi: invalid midi header");
+    if cfg!(all(debug_assertions, feature = "alloc")) {
+        assert!(err.source().is_some());
+    }
+    let err = parse_io(raw).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.get_ref().unwrap().is::<crate::Error>());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn seekable_write() {
+    use crate::io::{IoWrap, SeekableWrap};
+    use std::io::{self, Cursor, Seek, SeekFrom, Write};
+
+    /// Counts the seeks done, to make sure that the streaming path is taken.
+    struct CountSeeks(Cursor<Vec<u8>>, usize);
+    impl Write for CountSeeks {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+    impl Seek for CountSeeks {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.1 += 1;
+            self.0.seek(pos)
+        }
+    }
+
+    // Large enough to be written in parallel if the writer was not seekable
+    open! {file: "Clementi.mid"};
+    let (header, tracks) = crate::parse(&file).unwrap();
+    let tracks = tracks
+        .map(|track| track.unwrap().collect::<MidlyResult<Vec<_>>>().unwrap())
+        .collect::<Vec<_>>();
+    let mut buffered = Vec::new();
+    crate::write(&header, &tracks, &mut IoWrap(&mut buffered)).unwrap();
+    let mut streamed = SeekableWrap(CountSeeks(Cursor::new(Vec::new()), 0));
+    crate::write(&header, &tracks, &mut streamed).unwrap();
+    assert!(streamed.0 .1 > 0);
+    assert_eq!(streamed.0 .0.into_inner(), buffered);
+}
+
+#[test]
+fn running_status_reset() {
+    use crate::{Format, Header, MetaMessage, MidiMessage, Timing, TrackEventKind};
+
+    let note = TrackEventKind::Midi {
+        channel: 0.into(),
+        message: MidiMessage::NoteOn {
+            key: 60.into(),
+            vel: 64.into(),
+        },
+    };
+    let ev = |kind| TrackEvent {
+        delta: 0.into(),
+        kind,
+    };
+    let track = [
+        ev(note),
+        ev(note),
+        ev(TrackEventKind::Meta(MetaMessage::Marker(b"m"))),
+        ev(note),
+        ev(TrackEventKind::SysEx(&[0x7E, 0xF7])),
+        ev(note),
+        ev(TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+    ];
+    let header = Header::new(Format::SingleTrack, Timing::Metrical(96.into()));
+    let mut file = Vec::new();
+    crate::write(&header, [&track[..]].iter().copied(), &mut file).unwrap();
+    assert_eq!(
+        &file[22..],
+        &[
+            0, 0x90, 60, 64, //
+            0, 60, 64, // Running status
+            0, 0xFF, 0x06, 1, b'm', //
+            0, 0x90, 60, 64, // Full status after meta
+            0, 0xF0, 2, 0x7E, 0xF7, //
+            0, 0x90, 60, 64, // Full status after sysex
+            0, 0xFF, 0x2F, 0,
+        ][..]
+    );
+}
+
+#[test]
+fn running_status_cancelled_on_read() {
+    use crate::{EventIter, MidiMessage, TrackEventKind};
+
+    let note = TrackEventKind::Midi {
+        channel: 0.into(),
+        message: MidiMessage::NoteOn {
+            key: 60.into(),
+            vel: 64.into(),
+        },
+    };
+    for system in [
+        &[0xF0, 1, 0xF7][..],
+        &[0xF7, 1, 0xF8],
+        &[0xFF, 0x06, 1, b'm'],
+    ] {
+        let mut raw = vec![0, 0x90, 60, 64, 0, 60, 64, 0];
+        raw.extend_from_slice(system);
+        // A note without status byte after a system message must not reuse it as running status
+        raw.extend_from_slice(&[0, 60, 64]);
+        let events = EventIter::new(&raw).collect::<Vec<_>>();
+        assert_eq!(events[0].as_ref().unwrap().kind, note);
+        assert_eq!(events[1].as_ref().unwrap().kind, note);
+        assert!(events[2].is_ok());
+        if cfg!(feature = "strict") {
+            assert_eq!(events.len(), 4);
+            assert!(events[3].is_err());
+        } else {
+            assert_eq!(events.len(), 3);
+        }
+    }
+}
+
+#[test]
+fn status_bytes() {
+    use crate::{MetaMessage, MidiMessage, TrackEventKind};
+
+    let midi = |channel: u8, message| TrackEventKind::Midi {
+        channel: channel.into(),
+        message,
+    };
+    assert_eq!(
+        midi(
+            3,
+            MidiMessage::NoteOn {
+                key: 60.into(),
+                vel: 0.into()
+            }
+        )
+        .status_byte(),
+        0x93
+    );
+    assert_eq!(
+        midi(
+            15,
+            MidiMessage::PitchBend {
+                bend: crate::PitchBend::mid_raw_value()
+            }
+        )
+        .status_byte(),
+        0xEF
+    );
+    assert_eq!(TrackEventKind::SysEx(&[0xF7]).status_byte(), 0xF0);
+    assert_eq!(TrackEventKind::Escape(&[0xF8]).status_byte(), 0xF7);
+    assert_eq!(
+        TrackEventKind::Meta(MetaMessage::EndOfTrack).status_byte(),
+        0xFF
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn channel_message_bytes() {
+    use crate::{MidiMessage, PitchBend};
+
+    let on = MidiMessage::NoteOn {
+        key: 60.into(),
+        vel: 100.into(),
+    };
+    assert_eq!(on.channel_message_bytes(0.into()), [0x90, 60, 100]);
+    assert_eq!(on.channel_message_bytes(5.into()), [0x95, 60, 100]);
+    assert_eq!(
+        MidiMessage::ProgramChange { program: 7.into() }.channel_message_bytes(15.into()),
+        [0xCF, 7]
+    );
+    assert_eq!(
+        MidiMessage::PitchBend {
+            bend: PitchBend::mid_raw_value()
+        }
+        .channel_message_bytes(1.into()),
+        [0xE1, 0x00, 0x40]
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn meta_text() {
+    use crate::MetaMessage;
+
+    assert_eq!(
+        MetaMessage::TrackName("Grand piano".as_bytes())
+            .text()
+            .unwrap(),
+        "Grand piano"
+    );
+    assert_eq!(
+        MetaMessage::Lyric("naïve".as_bytes()).text().unwrap(),
+        "naïve"
+    );
+    // Invalid UTF-8 falls back to Latin-1
+    assert_eq!(MetaMessage::Lyric(b"na\xEFve").text().unwrap(), "naïve");
+    assert_eq!(
+        MetaMessage::Marker(b"verse").text_bytes(),
+        Some(&b"verse"[..])
+    );
+    assert_eq!(MetaMessage::EndOfTrack.text(), None);
+    assert_eq!(MetaMessage::SequencerSpecific(b"data").text_bytes(), None);
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn meta_text_with() {
+    use crate::{
+        encoding_rs::{SHIFT_JIS, UTF_8, WINDOWS_1252},
+        MetaMessage,
+    };
+
+    // "Piano" in katakana, encoded as Shift-JIS
+    let name = MetaMessage::TrackName(&[0x83, 0x73, 0x83, 0x41, 0x83, 0x6D]);
+    assert_eq!(name.text_with(SHIFT_JIS).unwrap(), "ピアノ");
+    // The Latin-1 fallback would decode the same bytes as mojibake
+    assert_ne!(name.text().unwrap(), "ピアノ");
+    assert_eq!(
+        MetaMessage::Lyric(b"na\xEFve")
+            .text_with(WINDOWS_1252)
+            .unwrap(),
+        "naïve"
+    );
+    // Malformed sequences are replaced instead of failing
+    assert_eq!(
+        MetaMessage::Text(b"a\xFFb").text_with(UTF_8).unwrap(),
+        "a\u{FFFD}b"
+    );
+    assert_eq!(MetaMessage::EndOfTrack.text_with(SHIFT_JIS), None);
+}
+
+#[test]
+fn signature_ranges() {
+    use crate::{EventIter, MetaMessage, TrackEventKind};
+
+    fn parse(raw: &[u8]) -> crate::Result<crate::TrackEvent<'_>> {
+        EventIter::new(raw).next().unwrap()
+    }
+    assert_eq!(
+        parse(&[0, 0xFF, 0x58, 4, 6, 3, 24, 8]).unwrap().kind,
+        TrackEventKind::Meta(MetaMessage::TimeSignature(6, 3, 24, 8))
+    );
+    assert_eq!(
+        parse(&[0, 0xFF, 0x59, 2, 0xF9, 1]).unwrap().kind,
+        TrackEventKind::Meta(MetaMessage::KeySignature(-7, true))
+    );
+    let bad_time = [0, 0xFF, 0x58, 4, 6, 8, 24, 8];
+    let bad_key = [0, 0xFF, 0x59, 2, 8, 0];
+    if cfg!(feature = "strict") {
+        assert!(parse(&bad_time).is_err());
+        assert!(parse(&bad_key).is_err());
+    } else {
+        assert_eq!(
+            parse(&bad_time).unwrap().kind,
+            TrackEventKind::Meta(MetaMessage::TimeSignature(6, 8, 24, 8))
+        );
+        assert_eq!(
+            parse(&bad_key).unwrap().kind,
+            TrackEventKind::Meta(MetaMessage::KeySignature(8, false))
+        );
+    }
+}
+
+#[test]
+fn scan_controllers() {
+    use crate::{EventIter, MidiMessage, TrackEventKind};
+
+    let raw = [
+        0, 0x90, 60, 64, 10, 60, 0, 0, 0xFF, 0x03, 2, b'a', b'b', 0, 0xF0, 1, 0xF7, 5, 0xB0, 64,
+        127, 0, 0xFF, 0x2F, 0,
+    ];
+    let mut events = EventIter::new(&raw);
+    assert_eq!(events.contains_controller(64.into()).ok(), Some(true));
+    assert_eq!(events.contains_controller(123.into()).ok(), Some(false));
+    assert_eq!(events.clone().count(), 6);
+    events.nth(4);
+    assert_eq!(events.contains_controller(64.into()).ok(), Some(false));
+    // A data byte with its top bit set is corrupted data, even if the event is complete
+    let bad_data = [0, 0x90, 0x90, 64, 0, 0xB0, 64, 127];
+    assert_eq!(EventIter::new(&bad_data).filter(|ev| ev.is_ok()).count(), 0);
+    assert_eq!(
+        EventIter::new(&bad_data)
+            .contains_controller(64.into())
+            .ok(),
+        if cfg!(feature = "strict") {
+            None
+        } else {
+            Some(false)
+        }
+    );
+    // Corrupted data is handled just like the iterator does
+    let truncated = EventIter::new(&raw[..19]);
+    if cfg!(feature = "strict") {
+        assert!(truncated.contains_controller(64.into()).is_err());
+        assert!(truncated.clone().any(|ev| ev.is_err()));
+    } else {
+        assert_eq!(truncated.contains_controller(64.into()).ok(), Some(false));
+        assert_eq!(truncated.clone().count(), 4);
+        assert!(truncated.clone().all(|ev| ev.is_ok()));
+    }
+
+    // Agree with full parsing on real files
+    let (_, tracks) = crate::parse(include_bytes!("../test-asset/Clementi.mid")).unwrap();
+    for track in tracks {
+        let track = track.unwrap();
+        for cc in [7, 10, 64, 121, 123] {
+            let parsed = track.clone().any(|ev| {
+                matches!(
+                    ev.map(|ev| ev.kind),
+                    Ok(TrackEventKind::Midi {
+                        message: MidiMessage::Controller { controller, .. },
+                        ..
+                    }) if controller == cc
+                )
+            });
+            assert_eq!(track.contains_controller(cc.into()).ok(), Some(parsed));
+        }
+    }
+}
+
+#[test]
+fn peek_events() {
+    use crate::EventIter;
+
+    let raw = [0, 0x90, 60, 64, 10, 62, 64, 0, 0xFF, 0x2F, 0];
+    let mut events = EventIter::new(&raw);
+    let mut peeked = Vec::new();
+    let mut consumed = Vec::new();
+    while let Some(ev) = events.peek() {
+        assert_eq!(
+            events.peek().unwrap().unwrap(),
+            ev.as_ref().unwrap().clone()
+        );
+        peeked.push(ev.unwrap());
+        consumed.push(events.next().unwrap().unwrap());
+    }
+    assert_eq!(peeked, consumed);
+    assert_eq!(consumed.len(), 3);
+    assert_eq!(consumed[1].delta, 10);
+    assert!(events.next().is_none());
+
+    let bytemapped = EventIter::new(&raw).bytemapped();
+    assert_eq!(bytemapped.peek().unwrap().unwrap().0, &raw[1..4]);
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn test_util_files() {
+    use crate::{test_util, Format, MetaMessage, Smf, Timing, TrackEventKind};
+
+    let bytes = test_util::one_note();
+    let smf = Smf::parse(&bytes).unwrap();
+    assert_eq!(smf.header.timing, Timing::Metrical(96.into()));
+    assert_eq!(smf.tracks[0].len(), 3);
+
+    let bytes = test_util::multi_track(3);
+    let smf = Smf::parse(&bytes).unwrap();
+    assert_eq!(smf.header.format, Format::Parallel);
+    assert_eq!(smf.tracks.len(), 4);
+    assert_eq!(smf.tracks[3].len(), 17);
+
+    let bytes = test_util::tempo_changes(5);
+    let smf = Smf::parse(&bytes).unwrap();
+    let tempos = smf.tracks[0]
+        .iter()
+        .filter(|ev| matches!(ev.kind, TrackEventKind::Meta(MetaMessage::Tempo(_))))
+        .count();
+    assert_eq!(tempos, 5);
+}
+
+#[test]
+fn payload_bytes() {
+    use crate::{num::u24, MetaMessage, TrackEventKind};
+
+    let kinds = [
+        TrackEventKind::SysEx(&[1, 2, 0xF7]),
+        TrackEventKind::Escape(&[3]),
+        TrackEventKind::Meta(MetaMessage::Lyric(b"la")),
+        TrackEventKind::Meta(MetaMessage::SequencerSpecific(&[0x41, 4])),
+        TrackEventKind::Meta(MetaMessage::Unknown(0x60, &[5])),
+        TrackEventKind::Meta(MetaMessage::Tempo(u24::new(500_000))),
+        TrackEventKind::Midi {
+            channel: 0.into(),
+            message: crate::MidiMessage::ProgramChange { program: 1.into() },
+        },
+    ];
+    let bytes = kinds.iter().map(|kind| kind.bytes()).collect::<Vec<_>>();
+    assert_eq!(
+        bytes,
+        [
+            Some(&[1, 2, 0xF7][..]),
+            Some(&[3][..]),
+            Some(&b"la"[..]),
+            Some(&[0x41, 4][..]),
+            Some(&[5][..]),
+            None,
+            None,
+        ]
+    );
+}
+
+#[test]
+fn raw_events() {
+    use crate::{EventIter, RawEvent, RawEventIter};
+
+    let raw = [
+        0, 0x90, 60, 64, 10, 62, 64, 0, 0xF0, 2, 1, 0xF7, 5, 0xFF, 0x01, 2, b'h', b'i', 0, 0xFF,
+        0x2F, 0,
+    ];
+    let events = RawEventIter::new(&raw)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        events,
+        [
+            RawEvent {
+                delta: 0.into(),
+                status: 0x90,
+                running: false,
+                data: &[60, 64],
+            },
+            RawEvent {
+                delta: 10.into(),
+                status: 0x90,
+                running: true,
+                data: &[62, 64],
+            },
+            RawEvent {
+                delta: 0.into(),
+                status: 0xF0,
+                running: false,
+                data: &[2, 1, 0xF7],
+            },
+            RawEvent {
+                delta: 5.into(),
+                status: 0xFF,
+                running: false,
+                data: &[0x01, 2, b'h', b'i'],
+            },
+            RawEvent {
+                delta: 0.into(),
+                status: 0xFF,
+                running: false,
+                data: &[0x2F, 0],
+            },
+        ]
+    );
+    let mut iter = EventIter::new(&raw).raw();
+    iter.next();
+    assert_eq!(iter.running_status(), Some(0x90));
+    assert_eq!(iter.decoded().count(), 4);
+    // Truncated messages and missing running status are errors only in strict mode
+    let strict = cfg!(feature = "strict");
+    assert_eq!(RawEventIter::new(&[0, 0x90, 60]).next().is_some(), strict);
+    #[cfg(feature = "alloc")]
+    assert_eq!(
+        RawEventIter::new(&[0, 60, 64])
+            .into_vec()
+            .ok()
+            .map(|evs| evs.len()),
+        if strict { None } else { Some(0) }
+    );
+}
+
+#[test]
+fn finished_reason() {
+    use crate::{EventIter, FinishReason};
+
+    let finish = |raw: &[u8]| {
+        let mut events = EventIter::new(raw);
+        let mut reasons = Vec::new();
+        while events.next().is_some() {
+            reasons.push(events.finished_reason());
+        }
+        reasons.push(events.finished_reason());
+        reasons
+    };
+    assert_eq!(
+        finish(&[0, 0x90, 60, 64, 0, 0xFF, 0x2F, 0]),
+        [
+            None,
+            Some(FinishReason::EndOfTrack),
+            Some(FinishReason::EndOfTrack)
+        ]
+    );
+    assert_eq!(
+        finish(&[0, 0xFF, 0x2F, 0, 0, 0x90, 60, 64]),
+        [
+            None,
+            Some(FinishReason::Exhausted),
+            Some(FinishReason::Exhausted)
+        ]
+    );
+    assert_eq!(finish(&[]), [Some(FinishReason::Exhausted)]);
+    let truncated = finish(&[0, 0x90, 60, 64, 0, 0x90, 60]);
+    assert_eq!(truncated.last(), Some(&Some(FinishReason::Malformed)));
+    assert_eq!(
+        truncated.len(),
+        if cfg!(feature = "strict") { 3 } else { 2 }
+    );
+}
+
+#[test]
+fn message_kinds() {
+    use crate::{MessageKind, MidiMessage, PitchBend};
+
+    let messages = [
+        MidiMessage::NoteOff {
+            key: 60.into(),
+            vel: 0.into(),
+        },
+        MidiMessage::NoteOn {
+            key: 60.into(),
+            vel: 64.into(),
+        },
+        MidiMessage::Aftertouch {
+            key: 60.into(),
+            vel: 10.into(),
+        },
+        MidiMessage::Controller {
+            controller: 7.into(),
+            value: 100.into(),
+        },
+        MidiMessage::ProgramChange { program: 3.into() },
+        MidiMessage::ChannelAftertouch { vel: 5.into() },
+        MidiMessage::PitchBend {
+            bend: PitchBend::mid_raw_value(),
+        },
+    ];
+    let kinds = messages.iter().map(|msg| msg.kind()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            MessageKind::NoteOff,
+            MessageKind::NoteOn,
+            MessageKind::Aftertouch,
+            MessageKind::Controller,
+            MessageKind::ProgramChange,
+            MessageKind::ChannelAftertouch,
+            MessageKind::PitchBend,
+        ]
+    );
+    let nibbles = messages
+        .iter()
+        .map(|msg| msg.status_nibble())
+        .collect::<Vec<_>>();
+    assert_eq!(nibbles, [0x8, 0x9, 0xA, 0xB, 0xC, 0xD, 0xE]);
+}
+
+#[test]
+fn meta_constructors() {
+    use crate::{Fps, MetaMessage, SmpteTime};
+
+    assert_eq!(
+        MetaMessage::time_signature_from(6, 8, 36, 8),
+        Some(MetaMessage::TimeSignature(6, 3, 36, 8))
+    );
+    assert_eq!(
+        MetaMessage::time_signature_from(4, 1, 24, 8),
+        Some(MetaMessage::TimeSignature(4, 0, 24, 8))
+    );
+    assert_eq!(MetaMessage::time_signature_from(4, 6, 24, 8), None);
+    assert_eq!(MetaMessage::time_signature_from(4, 0, 24, 8), None);
+    assert_eq!(
+        MetaMessage::key_signature_from(-3, true),
+        Some(MetaMessage::KeySignature(-3, true))
+    );
+    assert_eq!(MetaMessage::key_signature_from(8, false), None);
+    let time = SmpteTime::new(1, 2, 3, 4, 5, Fps::Fps25).unwrap();
+    assert_eq!(
+        MetaMessage::smpte_offset(time),
+        MetaMessage::SmpteOffset(time)
+    );
+}
+
+#[test]
+fn manufacturer_ids() {
+    use crate::{ManufacturerId, MetaMessage};
+
+    let (id, payload) = MetaMessage::SequencerSpecific(&[0x43, 0x7B, 0x01])
+        .manufacturer_id()
+        .unwrap();
+    assert_eq!(id, ManufacturerId::Short(0x43.into()));
+    assert_eq!(id.name(), Some("Yamaha"));
+    assert_eq!(payload, &[0x7B, 0x01]);
+
+    let (id, payload) = MetaMessage::SequencerSpecific(&[0x00, 0x20, 0x6B])
+        .manufacturer_id()
+        .unwrap();
+    assert_eq!(id, ManufacturerId::Extended(0x20.into(), 0x6B.into()));
+    assert_eq!(id.name(), Some("Arturia"));
+    assert!(payload.is_empty());
+
+    assert_eq!(ManufacturerId::Short(0x66.into()).name(), None);
+    assert_eq!(
+        MetaMessage::SequencerSpecific(&[0x00, 0x20]).manufacturer_id(),
+        None
+    );
+    assert_eq!(
+        MetaMessage::SequencerSpecific(&[0x80]).manufacturer_id(),
+        None
+    );
+    assert_eq!(MetaMessage::SequencerSpecific(&[]).manufacturer_id(), None);
+    assert_eq!(MetaMessage::Text(&[0x43]).manufacturer_id(), None);
+}
+
+#[test]
+fn event_size_hint() {
+    use crate::EventIter;
+
+    // Running status allows events as short as 2 bytes
+    let raw = [0, 0xC0, 1, 0, 2, 0, 3, 0, 0xFF, 0x2F, 0];
+    let mut events = EventIter::new(&raw);
+    assert_eq!(events.size_hint(), (1, Some(6)));
+    events.next();
+    assert_eq!(events.size_hint(), (1, Some(4)));
+    let count = events.clone().count();
+    assert_eq!(count, 3);
+    assert!(events.size_hint().0 <= count && count <= events.size_hint().1.unwrap());
+    events.by_ref().for_each(drop);
+    assert_eq!(events.size_hint(), (0, Some(0)));
+    assert_eq!(EventIter::new(&raw).bytemapped().size_hint(), (1, Some(6)));
+
+    // The lower bound assumes channel events with the longest possible delta time
+    let raw = [0xFF, 0xFF, 0xFF, 0x7F, 0x90, 60, 64].repeat(3);
+    let events = EventIter::new(&raw);
+    assert_eq!(events.size_hint(), (3, Some(11)));
+    assert_eq!(events.count(), 3);
+}
+
+#[test]
+fn pitch_bend_range() {
+    use crate::{MidiMessage, Parameter, ParameterChange, PitchBend, PitchBendRange, RpnTracker};
+
+    let cc = |controller: u8, value: u8| MidiMessage::Controller {
+        controller: controller.into(),
+        value: value.into(),
+    };
+    let mut rpn = RpnTracker::new();
+    assert_eq!(rpn.feed(1.into(), &cc(0x06, 12)), None);
+    rpn.feed(1.into(), &cc(0x65, 0));
+    rpn.feed(1.into(), &cc(0x64, 0));
+    assert_eq!(
+        rpn.selected(1.into()),
+        Some(Parameter::Registered(0.into()))
+    );
+    assert_eq!(rpn.selected(2.into()), None);
+    assert_eq!(
+        rpn.feed(1.into(), &cc(0x06, 12)),
+        Some(ParameterChange {
+            channel: 1.into(),
+            parameter: Parameter::Registered(0.into()),
+            value: (12 << 7).into(),
+        })
+    );
+    rpn.feed(1.into(), &cc(0x65, 0x7F));
+    rpn.feed(1.into(), &cc(0x64, 0x7F));
+    assert_eq!(rpn.selected(1.into()), None);
+
+    let mut range = PitchBendRange::new();
+    assert_eq!(range.range(3.into()), (2, 0));
+    for msg in [cc(0x65, 0), cc(0x64, 0), cc(0x06, 12), cc(0x26, 50)].iter() {
+        range.feed(3.into(), msg);
+    }
+    assert_eq!(range.range(3.into()), (12, 50));
+    assert_eq!(range.range(4.into()), (2, 0));
+    assert_eq!(
+        range.bend_semitones(3.into(), PitchBend::from_f32(-1.0)),
+        -12.5
+    );
+    assert_eq!(
+        range.bend_semitones(4.into(), PitchBend::from_f32(0.5)),
+        1.0
+    );
+    // Changing a non-registered parameter does not affect the range
+    for msg in [cc(0x63, 0), cc(0x62, 0), cc(0x06, 1)].iter() {
+        range.feed(3.into(), msg);
+    }
+    assert_eq!(range.range(3.into()), (12, 50));
+}
+
+#[test]
+fn corrupt_event_ends_iteration() {
+    use crate::EventIter;
+
+    // A note, followed by a forbidden system common status and some more notes
+    let raw = [0, 0x90, 60, 64, 0, 0xF1, 0, 0x90, 62, 64, 0, 0x90, 64, 64];
+    let mut events = EventIter::new(&raw);
+    assert!(events.next().unwrap().is_ok());
+    if cfg!(feature = "strict") {
+        assert!(events.next().unwrap().is_err());
+    }
+    for _ in 0..3 {
+        assert!(events.next().is_none());
+    }
+    assert!(events.unread().is_empty());
+    let collected = EventIter::new(&raw).collect::<MidlyResult<Vec<_>>>();
+    assert_eq!(collected.is_err(), cfg!(feature = "strict"));
+}
+
+#[test]
+fn big_endian_writers() {
+    use crate::io::{write_u16_be, write_u32_be, Cursor};
+
+    let mut buf = [0; 10];
+    let mut out = Cursor::new(&mut buf);
+    crate::io::Write::write(&mut out, b"XFIH").unwrap();
+    write_u32_be(2, &mut out).unwrap();
+    write_u16_be(0x1234, &mut out).unwrap();
+    assert!(write_u16_be(0, &mut out).is_err());
+    assert_eq!(buf, [b'X', b'F', b'I', b'H', 0, 0, 0, 2, 0x12, 0x34]);
+}
+
+#[test]
+fn midi_port_routing() {
+    use crate::{MetaMessage, MidiMessage, Routed, Routing, TrackEvent, TrackEventKind};
+
+    let ev = |kind| TrackEvent {
+        delta: 0.into(),
+        kind,
+    };
+    let note = |channel: u8| TrackEventKind::Midi {
+        channel: channel.into(),
+        message: MidiMessage::NoteOn {
+            key: 60.into(),
+            vel: 64.into(),
+        },
+    };
+    let track = [
+        ev(TrackEventKind::Meta(MetaMessage::TrackName(b"a"))),
+        ev(TrackEventKind::Meta(MetaMessage::MidiPort(1.into()))),
+        ev(TrackEventKind::Meta(MetaMessage::MidiChannel(3.into()))),
+        ev(TrackEventKind::SysEx(&[0x7E, 0xF7])),
+        ev(note(5)),
+        ev(TrackEventKind::Meta(MetaMessage::InstrumentName(b"b"))),
+    ];
+    let routing = Routed::new(track.iter())
+        .map(|(routing, _ev)| routing)
+        .collect::<Vec<_>>();
+    let route = |port: Option<u8>, channel: Option<u8>| Routing {
+        port: port.map(Into::into),
+        channel: channel.map(Into::into),
+    };
+    assert_eq!(
+        routing,
+        [
+            route(None, None),
+            route(Some(1), None),
+            route(Some(1), Some(3)),
+            route(Some(1), Some(3)),
+            route(Some(1), Some(5)),
+            route(Some(1), None),
+        ]
+    );
+}
+
+#[test]
+fn restricted_int_limits() {
+    use crate::num::{u14, u15, u24, u28, u4, u7};
+
+    assert_eq!(u4::MAX.as_int(), 0xF);
+    assert_eq!(u7::MAX.as_int(), 0x7F);
+    assert_eq!(u14::MAX.as_int(), 0x3FFF);
+    assert_eq!(u15::MAX.as_int(), 0x7FFF);
+    assert_eq!(u24::MAX.as_int(), 0xFF_FFFF);
+    assert_eq!(u28::MAX.as_int(), 0x0FFF_FFFF);
+    assert_eq!(u7::MAX, u7::max_value());
+    assert_eq!(u7::MIN.as_int(), 0);
+    assert_eq!(u28::BITS, 28);
+    assert_eq!(u7::new(100).as_int() as u32 + 100, 200);
+}
+
+#[test]
+fn restricted_int_iteration() {
+    use crate::num::{u14, u4, u7};
+
+    assert_eq!(u7::all().count(), 128);
+    assert!(u7::all().map(u7::as_int).eq(0..=127));
+    assert_eq!(u14::all().next_back(), Some(u14::MAX));
+    assert_eq!(
+        u4::range(3.into(), 6.into()).collect::<Vec<_>>(),
+        [u4::new(3), u4::new(4), u4::new(5)]
+    );
+    assert_eq!(u7::range(10.into(), 10.into()).count(), 0);
+    assert_eq!(u7::range(20.into(), 10.into()).count(), 0);
+}
+
+#[test]
+fn smpte_timing() {
+    use crate::{Fps, Timing};
+
+    // -25 fps, 40 ticks per frame
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 0, 0xE7, 40, //
+    ];
+    let (header, _tracks) = crate::parse(&raw).unwrap();
+    assert_eq!(header.timing, Timing::Timecode(Fps::Fps25, 40));
+    assert_eq!(header.timing.timecode(), Some((Fps::Fps25, 40)));
+    assert_eq!(header.timing.ticks_per_beat(), None);
+    assert_eq!(
+        header.timing.ticks_to_micros(1000, 500_000.into()),
+        Some(1_000_000)
+    );
+    let drop_frame = Timing::Timecode(Fps::Fps29, 100);
+    assert_eq!(drop_frame.ticks_to_micros(3000, 0.into()), Some(1_001_000));
+
+    let metrical = Timing::Metrical(96.into());
+    assert_eq!(metrical.ticks_per_beat(), Some(96.into()));
+    assert_eq!(metrical.timecode(), None);
+    assert_eq!(metrical.ticks_to_micros(48, 500_000.into()), Some(250_000));
+    assert_eq!(
+        Timing::Metrical(0.into()).ticks_to_micros(1, 500_000.into()),
+        None
+    );
+
+    assert_eq!(metrical.beats_to_ticks(1.5), Some(144));
+    // Septuplets round to the nearest tick, without accumulating error
+    let septuplets = (0..30)
+        .map(|i| metrical.beats_to_ticks(i as f64 / 7.0).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(septuplets[7], 96);
+    assert_eq!(septuplets[29], 398);
+    assert_eq!(metrical.beats_to_ticks(-1.0), None);
+    assert_eq!(metrical.beats_to_ticks(-0.001), None);
+    assert_eq!(metrical.beats_to_ticks(-0.0), Some(0));
+    assert_eq!(metrical.beats_to_ticks(f64::NAN), None);
+    assert_eq!(metrical.beats_to_ticks(1e30), None);
+    assert_eq!(header.timing.beats_to_ticks(1.0), None);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn note_balance_warnings() {
+    use crate::{Smf, Warning, WarningKind};
+
+    // Channel 0: [60 on] [60 on] [60 off] [61 off] [62 on]
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 22, //
+        0, 0x90, 60, 64, //
+        0, 60, 64, //
+        0, 0x80, 60, 0, //
+        0, 61, 0, //
+        0, 0x90, 62, 64, //
+        0, 0xFF, 0x2F, 0,
+    ];
+    let (smf, warnings) = Smf::parse_with_warnings(&raw).unwrap();
+    assert_eq!(smf.tracks[0].len(), 6);
+    let warning = |event, kind| Warning {
+        track: 0,
+        event,
+        kind,
+    };
+    assert_eq!(
+        warnings,
+        [
+            warning(
+                1,
+                WarningKind::DuplicateNoteOn {
+                    channel: 0.into(),
+                    key: 60.into()
+                }
+            ),
+            warning(
+                3,
+                WarningKind::NoteOffWithoutNoteOn {
+                    channel: 0.into(),
+                    key: 61.into()
+                }
+            ),
+            warning(
+                4,
+                WarningKind::UnterminatedNote {
+                    channel: 0.into(),
+                    key: 62.into()
+                }
+            ),
+        ]
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        "track 0, event 1: note 60 on channel 0 turned on while already playing"
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn non_canonical_varlen_warnings() {
+    use crate::{Smf, Warning, WarningKind};
+
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 21, //
+        0x81, 0x00, 0xC0, 1, // Canonical two-byte delta
+        0x80, 0x05, 2, // Redundant leading byte, with running status
+        0, 0xFF, 0x01, 0x80, 0x01, b'x', // Redundant leading byte in the data length
+        0, 0xF0, 1, 0xF7, //
+        0, 0xFF, 0x2F, 0,
+    ];
+    let (smf, warnings) = Smf::parse_with_warnings(&raw).unwrap();
+    let warn = |event| Warning {
+        track: 0,
+        event,
+        kind: WarningKind::NonCanonicalVarlen,
+    };
+    assert_eq!(warnings, vec![warn(1), warn(2)]);
+
+    // The writer always emits the canonical encoding
+    let mut file = Vec::new();
+    smf.write(&mut file).unwrap();
+    assert_eq!(Smf::parse_with_warnings(&file).unwrap().1, vec![]);
+    assert_eq!(file.len(), raw.len() - 2);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn parse_limits() {
+    use crate::{ParseOptions, Smf};
+
+    open! {file: "Clementi.mid"};
+    let smf = Smf::parse(&file).unwrap();
+    let events = smf.tracks.iter().map(Vec::len).sum::<usize>();
+    let limited = |max_track_len, max_total_events| {
+        Smf::parse_with_options(
+            &file,
+            &ParseOptions {
+                max_track_len,
+                max_total_events,
+            },
+        )
+    };
+    assert_eq!(
+        Smf::parse_with_options(&file, &ParseOptions::default()).unwrap(),
+        smf
+    );
+    assert_eq!(limited(file.len(), events).unwrap(), smf);
+    assert!(limited(file.len(), events - 1).is_err());
+    assert!(limited(16, events).is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn sysex_termination() {
+    use crate::{Smf, TrackEventKind, Warning, WarningKind, WriteOptions};
+
+    // [complete sysex] [split sysex: F0 packet, F7 packet] [unterminated sysex]
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 26, //
+        0, 0xF0, 3, 0x7E, 0x01, 0xF7, //
+        0, 0xF0, 1, 0x7E, //
+        10, 0xF7, 2, 0x01, 0xF7, //
+        0, 0xF0, 2, 0x7E, 0x02, //
+        0, 0xFF, 0x2F, 0,
+    ];
+    if cfg!(feature = "strict") {
+        assert!(Smf::parse(&raw).is_err());
+        return;
+    }
+    let (smf, warnings) = Smf::parse_with_warnings(&raw).unwrap();
+    assert_eq!(
+        warnings,
+        [Warning {
+            track: 0,
+            event: 3,
+            kind: WarningKind::UnterminatedSysEx,
+        }]
+    );
+    let payloads = smf.tracks[0]
+        .iter()
+        .map(|ev| ev.kind.sysex_payload())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        payloads,
+        [
+            Some(&[0x7E, 0x01][..]),
+            Some(&[0x7E][..]),
+            None,
+            Some(&[0x7E, 0x02][..]),
+            None,
+        ]
+    );
+    assert_eq!(
+        TrackEventKind::SysEx(&[0xF7]).sysex_payload(),
+        Some(&[][..])
+    );
+
+    // Rewriting leaves the split message alone, but terminates the unterminated one
+    let options = WriteOptions {
+        terminate_sysex: true,
+        ..WriteOptions::default()
+    };
+    let mut rewritten = Vec::new();
+    smf.write_with_options(&options, &mut rewritten).unwrap();
+    assert_eq!(rewritten.len(), raw.len() + 1);
+    let (fixed, warnings) = Smf::parse_with_warnings(&rewritten).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(fixed.tracks[0][..3], smf.tracks[0][..3]);
+    assert_eq!(
+        fixed.tracks[0][3].kind,
+        TrackEventKind::SysEx(&[0x7E, 0x02, 0xF7])
+    );
+    let mut plain = Vec::new();
+    smf.write(&mut plain).unwrap();
+    assert_eq!(plain[22..], raw[22..]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn sysex_packets() {
+    use crate::{num::u28, write_with_options, Header, TrackEvent, TrackEventKind, WriteOptions};
+
+    let sysex = |delta: u32, kind| TrackEvent {
+        delta: u28::new(delta),
+        kind,
+    };
+    let tracks = vec![vec![
+        sysex(5, TrackEventKind::SysEx(&[1, 2, 3, 4, 5, 0xF7])),
+        sysex(0, TrackEventKind::SysEx(&[6, 7])),
+    ]];
+    let options = WriteOptions {
+        ensure_end_of_track: false,
+        terminate_sysex: true,
+        max_sysex_packet: Some(4),
+        ..WriteOptions::default()
+    };
+    let mut out = Vec::new();
+    write_with_options(&Header::default(), &tracks, &options, &mut out).unwrap();
+    assert_eq!(
+        out[22..],
+        [
+            5, 0xF0, 4, 1, 2, 3, 4, //
+            0, 0xF7, 2, 5, 0xF7, //
+            0, 0xF0, 3, 6, 7, 0xF7,
+        ]
+    );
+
+    // The terminating byte may end up in a packet of its own
+    let options = WriteOptions {
+        max_sysex_packet: Some(2),
+        ..options
+    };
+    let mut out = Vec::new();
+    write_with_options(&Header::default(), &tracks[..], &options, &mut out).unwrap();
+    assert_eq!(
+        out[22..],
+        [
+            5, 0xF0, 2, 1, 2, //
+            0, 0xF7, 2, 3, 4, //
+            0, 0xF7, 2, 5, 0xF7, //
+            0, 0xF0, 2, 6, 7, //
+            0, 0xF7, 1, 0xF7,
+        ]
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn trailing_track_bytes() {
+    use crate::{MetaMessage, Smf, TrackEventKind};
+
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 2, 0, 96, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 11, //
+        0, 0x90, 60, 64, //
+        0, 0xFF, 0x2F, 0, //
+        0x12, 0x34, 0x56, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 4, //
+        0, 0xFF, 0x2F, 0,
+    ];
+    if cfg!(feature = "strict") {
+        assert!(Smf::parse_with_trailing(&raw).is_err());
+        return;
+    }
+    let (smf, trailing) = Smf::parse_with_trailing(&raw).unwrap();
+    assert_eq!(trailing, [&[0x12, 0x34, 0x56][..], &[][..]]);
+    assert_eq!(smf.tracks[0].len(), 2);
+    assert_eq!(
+        smf.tracks[0][1].kind,
+        TrackEventKind::Meta(MetaMessage::EndOfTrack)
+    );
+    assert_eq!(smf.tracks, Smf::parse(&raw).unwrap().tracks);
+
+    let mut out = Vec::new();
+    smf.write_with_trailing(&trailing, &mut out).unwrap();
+    assert_eq!(out[..], raw[..]);
+    let mut out = Vec::new();
+    smf.write_with_trailing(&[], &mut out).unwrap();
+    assert_eq!(out.len(), raw.len() - 3);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn unknown_chunks() {
+    use crate::{Smf, UnknownChunk};
+
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 2, 0, 96, //
+        b'X', b'F', b'I', b'H', 0, 0, 0, 2, 0xAB, 0xCD, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 4, 0, 0xFF, 0x2F, 0, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 4, 0, 0xFF, 0x2F, 0, //
+        b'X', b'F', b'K', b'M', 0, 0, 0, 0,
+    ];
+    let (smf, chunks) = Smf::parse_with_chunks(&raw).unwrap();
+    assert_eq!(smf, Smf::parse(&raw).unwrap());
+    assert_eq!(
+        chunks,
+        vec![
+            UnknownChunk {
+                tag: *b"XFIH",
+                data: &[0xAB, 0xCD],
+                position: 0,
+            },
+            UnknownChunk {
+                tag: *b"XFKM",
+                data: &[],
+                position: 2,
+            },
+        ]
+    );
+    let mut rewritten = Vec::new();
+    smf.write_with_chunks(&chunks, &mut rewritten).unwrap();
+    assert_eq!(&rewritten[..], &raw[..]);
+
+    // Chunks past the end are written after the last track
+    let late = [UnknownChunk {
+        position: 10,
+        ..chunks[0]
+    }];
+    let mut rewritten = Vec::new();
+    smf.write_with_chunks(&late, &mut rewritten).unwrap();
+    assert!(rewritten.ends_with(&raw[14..24]));
+
+    // Trailing garbage too short to be a chunk is handled just like `Smf::parse` does
+    let mut garbage = raw.to_vec();
+    garbage.extend_from_slice(&[0x12, 0x34, 0x56]);
+    if cfg!(feature = "strict") {
+        assert!(Smf::parse(&garbage).is_err());
+        assert!(Smf::parse_with_chunks(&garbage).is_err());
+    } else {
+        let (smf, garbage_chunks) = Smf::parse_with_chunks(&garbage).unwrap();
+        assert_eq!(smf, Smf::parse(&garbage).unwrap());
+        assert_eq!(garbage_chunks, chunks);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn preserving_roundtrip() {
+    use crate::{Smf, WriteOptions};
+
+    // Unknown chunks, trailing garbage and no running status, all in the same file
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, //
+        b'X', b'F', b'I', b'H', 0, 0, 0, 2, 0xAB, 0xCD, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 14, //
+        0, 0x90, 60, 64, //
+        0, 0x90, 64, 64, //
+        0, 0xFF, 0x2F, 0, //
+        0x12, 0x34, //
+        b'X', b'F', b'K', b'M', 0, 0, 0, 0,
+    ];
+    if cfg!(feature = "strict") {
+        assert!(Smf::parse_preserving(&raw).is_err());
+        return;
+    }
+    let (smf, preserved) = Smf::parse_preserving(&raw).unwrap();
+    assert_eq!(preserved.chunks, Smf::parse_with_chunks(&raw).unwrap().1);
+    assert_eq!(
+        preserved.trailing,
+        Smf::parse_with_trailing(&raw).unwrap().1
+    );
+    assert_eq!(preserved.chunks.len(), 2);
+    assert_eq!(smf.tracks[0].len(), 3);
+
+    let options = WriteOptions {
+        running_status: false,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    smf.write_preserving(&preserved, &options, &mut out)
+        .unwrap();
+    assert_eq!(out[..], raw[..]);
+
+    // With the default options the second note uses running status
+    let mut out = Vec::new();
+    smf.write_preserving(&preserved, &WriteOptions::default(), &mut out)
+        .unwrap();
+    assert_eq!(out.len(), raw.len() - 1);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn minimal_file() {
+    use crate::{roundtrip_equal, Format, Smf, Timing};
+
+    let smf = Smf::empty(Format::SingleTrack, 96);
+    let mut file = Vec::new();
+    smf.write(&mut file).unwrap();
+    assert_eq!(
+        file,
+        [
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, //
+            b'M', b'T', b'r', b'k', 0, 0, 0, 4, //
+            0, 0xFF, 0x2F, 0,
+        ]
+    );
+    // The file must also be accepted with the `strict` feature
+    assert_eq!(Smf::parse(&file).unwrap(), smf);
+    assert!(roundtrip_equal(&file).unwrap());
+    assert_eq!(
+        Smf::empty(Format::Parallel, 0xFFFF).header.timing,
+        Timing::Metrical(0x7FFF.into())
+    );
+}
+
+#[test]
+fn header_only() {
+    use crate::{Format, Header, Timing};
+
+    // Only the header chunk is present, the tracks are never looked at
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 3, 0, 96, //
+        b'M', b'T', b'r', b'k', 0xFF, 0xFF, 0xFF, 0xFF,
+    ];
+    assert_eq!(
+        Header::parse(&raw).unwrap(),
+        (
+            Header::new(Format::Parallel, Timing::Metrical(96.into())),
+            3
+        )
+    );
+    assert!(Header::parse(b"MThd").is_err());
+    assert!(Header::parse(b"not a midi file").is_err());
+    open! {file: "Clementi.mid"};
+    assert_eq!(
+        Header::parse(&file).unwrap().0,
+        crate::parse(&file).unwrap().0
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn structural_hash() {
+    use crate::Smf;
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+    };
+
+    let hash = |smf: &Smf| {
+        let mut hasher = DefaultHasher::new();
+        smf.hash(&mut hasher);
+        hasher.finish()
+    };
+    // The same notes, with and without running status
+    let explicit = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 12, //
+        0, 0x90, 60, 64, 0, 0x90, 62, 64, 0, 0xFF, 0x2F, 0,
+    ];
+    let running = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 11, //
+        0, 0x90, 60, 64, 0, 62, 64, 0, 0xFF, 0x2F, 0,
+    ];
+    let a = Smf::parse(&explicit).unwrap();
+    let b = Smf::parse(&running).unwrap();
+    assert_eq!(a, b);
+    assert_eq!(hash(&a), hash(&b));
+    let mut c = a.clone();
+    c.tracks[0][1].delta = 1.into();
+    assert_ne!(a, c);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn schedule() {
+    use crate::{
+        live::LiveEvent, Format, Fps, Header, MetaMessage, MidiMessage, Scheduler, Smf, Timing,
+        TrackEvent, TrackEventKind,
+    };
+    use std::time::Duration;
+
+    let ev = |delta: u32, kind| TrackEvent {
+        delta: delta.into(),
+        kind,
+    };
+    let note = |key: u8| MidiMessage::NoteOn {
+        key: key.into(),
+        vel: 64.into(),
+    };
+    let on = |key| TrackEventKind::Midi {
+        channel: 0.into(),
+        message: note(key),
+    };
+    let live = |key| LiveEvent::Midi {
+        channel: 0.into(),
+        message: note(key),
+    };
+    let tempo = |t: u32| TrackEventKind::Meta(MetaMessage::Tempo(t.into()));
+    let end = TrackEventKind::Meta(MetaMessage::EndOfTrack);
+    let schedule = |smf: &Smf<'static>| {
+        Scheduler::new(smf)
+            .unwrap()
+            .map(|(time, ev)| (time.as_micros() as u64, ev))
+            .collect::<Vec<_>>()
+    };
+
+    let mut smf = Smf::new(Header::new(Format::Parallel, Timing::Metrical(96.into())));
+    smf.tracks = vec![
+        vec![ev(96, tempo(250_000)), ev(0, end)],
+        vec![ev(48, on(60)), ev(96, on(62)), ev(0, end)],
+    ];
+    assert_eq!(
+        schedule(&smf),
+        vec![(250_000, live(60)), (625_000, live(62))]
+    );
+
+    let slow = Scheduler::with_initial_tempo(&smf, 1_000_000.into())
+        .unwrap()
+        .map(|(time, _)| time.as_micros() as u64)
+        .collect::<Vec<_>>();
+    assert_eq!(slow, vec![500_000, 1_125_000]);
+
+    smf.header.format = Format::Sequential;
+    assert_eq!(
+        schedule(&smf),
+        vec![(625_000, live(60)), (875_000, live(62))]
+    );
+
+    // Timecode timing ignores tempo changes
+    smf.header = Header::new(Format::Parallel, Timing::Timecode(Fps::Fps25, 40));
+    assert_eq!(
+        schedule(&smf),
+        vec![(48_000, live(60)), (144_000, live(62))]
+    );
+    assert_eq!(
+        Scheduler::new(&smf).unwrap().next().unwrap().0,
+        Duration::from_millis(48)
+    );
+
+    smf.header = Header::new(Format::Parallel, Timing::Metrical(0.into()));
+    assert!(Scheduler::new(&smf).is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn concatenated_files() {
+    use crate::{Format, Smf};
+
+    let mut a = Vec::new();
+    Smf::empty(Format::SingleTrack, 96).write(&mut a).unwrap();
+    let mut b = Vec::new();
+    let mut parallel = Smf::empty(Format::Parallel, 480);
+    parallel.tracks.push(parallel.tracks[0].clone());
+    parallel.write(&mut b).unwrap();
+    let mut raw = a.clone();
+    raw.extend_from_slice(&b);
+    raw.extend_from_slice(&a);
+
+    let files = Smf::parse_multi(&raw).unwrap();
+    assert_eq!(files.len(), 3);
+    assert_eq!(files[0], (Smf::empty(Format::SingleTrack, 96), a.len()));
+    assert_eq!(files[1], (parallel, b.len()));
+    assert_eq!(files[2].1, a.len());
+    assert_eq!(Smf::parse_multi(&[]).unwrap(), vec![]);
+    raw.extend_from_slice(b"garbage");
+    assert!(Smf::parse_multi(&raw).is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn encoded_len() {
+    use crate::{Smf, WriteOptions};
+
+    open! {file: "Clementi.mid"};
+    let mut smf = Smf::parse(&file).unwrap();
+    let mut out = Vec::new();
+    smf.write(&mut out).unwrap();
+    assert_eq!(smf.encoded_len(), out.len());
+
+    // Tracks lacking an `EndOfTrack` event and custom options are accounted for
+    smf.tracks[0].pop();
+    smf.tracks[1][3].delta = 1000.into();
+    let options = WriteOptions {
+        max_delta: Some(100.into()),
+        ..WriteOptions::default()
+    };
+    for options in [WriteOptions::default(), options] {
+        out.clear();
+        smf.write_with_options(&options, &mut out).unwrap();
+        assert_eq!(smf.encoded_len_with_options(&options), out.len());
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn undeclared_tracks() {
+    use crate::Smf;
+
+    // The header declares zero tracks, but two tracks follow
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 0, 0, 96, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 4, //
+        0, 0xFF, 0x2F, 0, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 4, //
+        0, 0xFF, 0x2F, 0,
+    ];
+    if cfg!(feature = "strict") {
+        assert!(Smf::parse(&raw).is_err());
+    } else {
+        let smf = Smf::parse(&raw).unwrap();
+        assert_eq!(smf.tracks.len(), 2);
+        // The actual track count is written back
+        let mut file = Vec::new();
+        smf.write(&mut file).unwrap();
+        assert_eq!(file[10..12], [0, 2]);
+    }
+    let (_header, tracks) = crate::parse(&raw).unwrap();
+    assert_eq!(tracks.count(), 2);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn roundtrip_equality() {
+    use crate::roundtrip_equal;
+
+    open! {file: "Clementi.mid"};
+    assert!(roundtrip_equal(&file).unwrap());
+    // The RIFF wrapper is lost when rewriting
+    open! {rmi: "Beethoven.rmi"};
+    assert!(!roundtrip_equal(&rmi).unwrap());
+    // A non-minimal delta time is normalized when rewriting
+    let raw = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, //
+        b'M', b'T', b'r', b'k', 0, 0, 0, 9, //
+        0, 0x90, 60, 64, //
+        0x80, 0, 0xFF, 0x2F, 0,
+    ];
+    assert!(!roundtrip_equal(&raw).unwrap());
+    assert!(roundtrip_equal(&b"not a midi file"[..]).is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn iter_adapters() {
+    use crate::{
+        iter::{AbsoluteTime, ChannelFilter, MidiOnly, Note, Notes, Rests},
+        MetaMessage, MidiMessage, TrackEvent, TrackEventKind,
+    };
+
+    let ev = |delta: u32, kind| TrackEvent {
+        delta: delta.into(),
+        kind,
+    };
+    let note = |channel: u8, key: u8, vel: u8| TrackEventKind::Midi {
+        channel: channel.into(),
+        message: MidiMessage::NoteOn {
+            key: key.into(),
+            vel: vel.into(),
+        },
+    };
+    let off = |channel: u8, key: u8| TrackEventKind::Midi {
+        channel: channel.into(),
+        message: MidiMessage::NoteOff {
+            key: key.into(),
+            vel: 0.into(),
+        },
+    };
+    let tempo = TrackEventKind::Meta(MetaMessage::Tempo(500_000.into()));
+    let track = [
+        ev(0, tempo),
+        ev(0, note(0, 60, 100)),
+        ev(10, note(1, 60, 90)),
+        ev(0, note(0, 60, 80)),
+        ev(10, note(0, 60, 0)),
+        ev(10, off(1, 60)),
+        ev(0, off(0, 60)),
+        ev(10, note(0, 64, 70)),
+        ev(10, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+    ];
+
+    let timed = AbsoluteTime::new(track.iter());
+    assert_eq!(timed.clone().count(), track.len());
+    assert_eq!(timed.clone().nth(4), Some((20, note(0, 60, 0))));
+    assert_eq!(MidiOnly::new(timed.clone()).count(), track.len() - 2);
+    assert_eq!(
+        ChannelFilter::new(timed.clone(), 1.into()).collect::<Vec<_>>(),
+        vec![(10, note(1, 60, 90)), (30, off(1, 60))]
+    );
+
+    let mk = |start, end, channel: u8, key: u8, vel: u8| Note {
+        start,
+        end,
+        channel: channel.into(),
+        key: key.into(),
+        vel: vel.into(),
+    };
+    assert_eq!(
+        Notes::new(timed.clone()).collect::<Vec<_>>(),
+        vec![
+            mk(0, 20, 0, 60, 100),
+            mk(10, 30, 1, 60, 90),
+            mk(10, 30, 0, 60, 80),
+            mk(40, 50, 0, 64, 70),
+        ]
+    );
+    assert!(crate::iter::notes(&track).eq(Notes::new(timed.clone())));
+
+    // The `EndOfTrack` event is filtered out, so the last note never ends
+    let channel_zero = Notes::new(ChannelFilter::new(timed, 0.into()));
+    assert_eq!(
+        channel_zero.map(|note| note.duration()).collect::<Vec<_>>(),
+        vec![20, 20, 0]
+    );
+
+    assert_eq!(
+        crate::iter::rests(&track, 0.into()).collect::<Vec<_>>(),
+        vec![(30, 10)]
+    );
+    assert_eq!(crate::iter::rests(&track, 1.into()).count(), 0);
+
+    // Overlapping notes of the same key, released first-in first-out
+    let overlapping = [
+        ev(0, note(0, 60, 100)),
+        ev(10, note(0, 60, 90)),
+        ev(10, off(0, 60)),
+        ev(30, off(0, 60)),
+        ev(20, note(0, 60, 80)),
+        ev(10, off(0, 60)),
+        ev(10, off(0, 60)),
+    ];
+    assert_eq!(
+        crate::iter::notes(&overlapping).collect::<Vec<_>>(),
+        vec![
+            mk(0, 20, 0, 60, 100),
+            mk(10, 50, 0, 60, 90),
+            mk(70, 80, 0, 60, 80),
+        ]
+    );
+    assert_eq!(
+        crate::iter::rests(&overlapping, 0.into()).collect::<Vec<_>>(),
+        vec![(50, 20)]
+    );
+
+    let phrase = [
+        mk(0, 10, 0, 60, 64),
+        mk(10, 20, 0, 62, 64),
+        mk(15, 40, 0, 64, 64),
+        mk(20, 25, 0, 65, 64),
+        mk(50, 60, 0, 67, 64),
+        mk(61, 62, 0, 69, 64),
+    ];
+    assert_eq!(
+        Rests::new(phrase.iter().copied()).collect::<Vec<_>>(),
+        vec![(40, 10), (60, 1)]
+    );
+}
+
+/// Test the track editing utilities.
+#[cfg(feature = "alloc")]
+mod edit {
+    use crate::{edit, MetaMessage, MidiMessage, TrackEvent, TrackEventKind};
+
+    fn ev(delta: u32, kind: TrackEventKind) -> TrackEvent {
+        TrackEvent {
+            delta: delta.into(),
+            kind,
+        }
+    }
+    fn note_on(key: u8) -> TrackEventKind<'static> {
+        TrackEventKind::Midi {
+            channel: 0.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: 64.into(),
+            },
+        }
+    }
+    const END: TrackEventKind = TrackEventKind::Meta(MetaMessage::EndOfTrack);
+
+    #[test]
+    fn plain_delta_ticks() {
+        assert_eq!(TrackEvent::try_new(96, END), Some(ev(96, END)));
+        assert_eq!(ev(96, END).delta_ticks(), 96);
+        assert_eq!(
+            TrackEvent::try_new((1 << 28) - 1, END).map(|ev| ev.delta_ticks()),
+            Some((1 << 28) - 1)
+        );
+        assert_eq!(TrackEvent::try_new(1 << 28, END), None);
+    }
+
+    #[test]
+    fn event_conversions() {
+        assert_eq!(TrackEventKind::from(MetaMessage::EndOfTrack), END);
+        assert_eq!(TrackEvent::from(END), ev(0, END));
+        let msg = MidiMessage::NoteOn {
+            key: 64.into(),
+            vel: 64.into(),
+        };
+        assert_eq!(TrackEventKind::midi(0.into(), msg), note_on(64));
+        let ev: TrackEvent = TrackEventKind::midi(0.into(), msg).into();
+        assert_eq!(ev.delta, 0);
+    }
+
+    #[test]
+    fn set_track_name() {
+        let mut track = vec![ev(0, note_on(60)), ev(10, END)];
+        edit::set_track_name(&mut track, "piano");
+        assert_eq!(
+            track,
+            vec![
+                ev(0, TrackEventKind::Meta(MetaMessage::TrackName(b"piano"))),
+                ev(0, note_on(60)),
+                ev(10, END),
+            ]
+        );
+        edit::set_track_name(&mut track, "organ");
+        assert_eq!(track.len(), 3);
+        assert_eq!(
+            track[0],
+            ev(0, TrackEventKind::Meta(MetaMessage::TrackName(b"organ")))
+        );
+    }
+
+    #[test]
+    fn track_queries() {
+        let on = |channel: u8, key: u8| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: 64.into(),
+            },
+        };
+        let name = TrackEventKind::Meta(MetaMessage::TrackName(b"bass"));
+        let mut track = vec![
+            ev(0, name),
+            ev(0, on(2, 40)),
+            ev(10, on(5, 120)),
+            ev(5, on(5, 3)),
+            ev(5, END),
+        ];
+        assert_eq!(edit::track_name(&track), Some(&b"bass"[..]));
+        assert_eq!(edit::track_name(&track[1..]), None);
+        assert_eq!(edit::duration_ticks(&track), 20);
+        assert_eq!(edit::duration_ticks(&[]), 0);
+        let mut used = [false; 16];
+        used[2] = true;
+        used[5] = true;
+        assert_eq!(edit::channels_used(&track), used);
+
+        edit::transpose(&mut track, 12);
+        assert_eq!(
+            track,
+            vec![
+                ev(0, name),
+                ev(0, on(2, 52)),
+                ev(10, on(5, 127)),
+                ev(5, on(5, 15)),
+                ev(5, END),
+            ]
+        );
+        edit::transpose(&mut track, -20);
+        assert_eq!(track[3], ev(5, on(5, 0)));
+    }
+
+    #[test]
+    fn transpose_musical() {
+        let on = |channel: u8, key: u8| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: 64.into(),
+            },
+        };
+        let mut track = vec![ev(0, on(0, 60)), ev(0, on(9, 36)), ev(10, END)];
+        edit::transpose_musical(&mut track, -2, 1 << 9);
+        assert_eq!(track, vec![ev(0, on(0, 58)), ev(0, on(9, 36)), ev(10, END)]);
+        edit::transpose_musical(&mut track, 2, 0);
+        assert_eq!(track, vec![ev(0, on(0, 60)), ev(0, on(9, 38)), ev(10, END)]);
+        edit::transpose_musical(&mut track, 1, 1 << 0 | 1 << 9);
+        assert_eq!(track, vec![ev(0, on(0, 60)), ev(0, on(9, 38)), ev(10, END)]);
+    }
+
+    #[test]
+    fn strip_meta() {
+        let tempo = TrackEventKind::Meta(MetaMessage::Tempo(500_000.into()));
+        let text = TrackEventKind::Meta(MetaMessage::Text(b"hi"));
+        let track = vec![
+            ev(0, tempo),
+            ev(5, text),
+            ev(5, note_on(60)),
+            ev(3, text),
+            ev(0, tempo),
+            ev(2, note_on(62)),
+            ev(10, END),
+        ];
+        let mut stripped = track.clone();
+        edit::strip_meta(&mut stripped, false);
+        assert_eq!(
+            stripped,
+            vec![ev(10, note_on(60)), ev(5, note_on(62)), ev(10, END)]
+        );
+        let mut stripped = track;
+        edit::strip_meta(&mut stripped, true);
+        assert_eq!(
+            stripped,
+            vec![
+                ev(0, tempo),
+                ev(10, note_on(60)),
+                ev(3, tempo),
+                ev(2, note_on(62)),
+                ev(10, END),
+            ]
+        );
+    }
+
+    #[test]
+    fn build_from_defaults() {
+        use crate::{num::u15, Format, Header, Smf, Timing};
+
+        let header = Header::default();
+        assert_eq!(header.format, Format::Parallel);
+        assert_eq!(header.timing, Timing::Metrical(u15::from(480)));
+        let mut smf = Smf::new(header);
+        smf.tracks.push(edit::empty_track());
+        let mut bytes = Vec::new();
+        smf.write(&mut bytes).unwrap();
+        assert_eq!(Smf::parse(&bytes).unwrap(), smf);
+    }
+
+    #[test]
+    fn rescale_resolution() {
+        let mut track = vec![
+            ev(0, note_on(60)),
+            ev(1, note_on(61)),
+            ev(1, note_on(62)),
+            ev(1, note_on(63)),
+            ev(96, END),
+        ];
+        edit::rescale_resolution(&mut track, 96, 480).unwrap();
+        let deltas = track.iter().map(|ev| ev.delta.as_int()).collect::<Vec<_>>();
+        assert_eq!(deltas, [0, 5, 5, 5, 480]);
+        // Rounding happens on absolute ticks, so the error does not accumulate
+        edit::rescale_resolution(&mut track, 480, 96).unwrap();
+        let deltas = track.iter().map(|ev| ev.delta.as_int()).collect::<Vec<_>>();
+        assert_eq!(deltas, [0, 1, 1, 1, 96]);
+        edit::rescale_resolution(&mut track, 3, 2).unwrap();
+        let deltas = track.iter().map(|ev| ev.delta.as_int()).collect::<Vec<_>>();
+        assert_eq!(deltas, [0, 1, 0, 1, 64]);
+
+        let mut huge = vec![ev(0x0FFF_FFFF, END)];
+        assert!(edit::rescale_resolution(&mut huge, 1, 2).is_err());
+        assert_eq!(huge, vec![ev(0x0FFF_FFFF, END)]);
+    }
+
+    #[test]
+    fn end_of_track() {
+        use crate::{write_with_options, Header, Smf, WriteOptions};
+
+        let mut track = vec![ev(0, note_on(60)), ev(4, END), ev(6, note_on(62))];
+        edit::ensure_end_of_track(&mut track);
+        assert_eq!(
+            track,
+            vec![ev(0, note_on(60)), ev(10, note_on(62)), ev(0, END)]
+        );
+        edit::ensure_end_of_track(&mut track);
+        assert_eq!(track.len(), 3);
+
+        track.last_mut().unwrap().delta = 7.into();
+        assert_eq!(edit::remove_end_of_track(&mut track).as_int(), 7);
+        assert_eq!(track, vec![ev(0, note_on(60)), ev(10, note_on(62))]);
+
+        // The writer terminates tracks automatically unless told not to
+        let header = Header::default();
+        let tracks = vec![track];
+        let mut bytes = Vec::new();
+        crate::write(&header, &tracks, &mut bytes).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        assert_eq!(smf.tracks[0].last().unwrap().kind, END);
+        let options = WriteOptions {
+            ensure_end_of_track: false,
+            ..WriteOptions::default()
+        };
+        let mut raw = Vec::new();
+        write_with_options(&header, &tracks, &options, &mut raw).unwrap();
+        assert_eq!(raw.len() + 4, bytes.len());
+    }
+
+    #[test]
+    fn max_delta() {
+        use crate::{write_with_options, Header, Smf, WriteOptions};
+
+        let header = Header::default();
+        let tracks = vec![vec![ev(0, note_on(60)), ev(250, note_on(62)), ev(100, END)]];
+        let options = WriteOptions {
+            max_delta: Some(100.into()),
+            ..WriteOptions::default()
+        };
+        let mut raw = Vec::new();
+        write_with_options(&header, &tracks, &options, &mut raw).unwrap();
+        let filler = TrackEventKind::Meta(MetaMessage::Text(b""));
+        assert_eq!(
+            Smf::parse(&raw).unwrap().tracks[0],
+            vec![
+                ev(0, note_on(60)),
+                ev(100, filler),
+                ev(100, filler),
+                ev(50, note_on(62)),
+                ev(100, END),
+            ]
+        );
+    }
+
+    #[test]
+    fn note_off_as_note_on_zero() {
+        use crate::{write_with_options, Header, Smf, WriteOptions};
+
+        let midi = |message| TrackEventKind::Midi {
+            channel: 0.into(),
+            message,
+        };
+        let off = |key: u8| {
+            midi(MidiMessage::NoteOff {
+                key: key.into(),
+                vel: 30.into(),
+            })
+        };
+        let on_zero = |key: u8| {
+            midi(MidiMessage::NoteOn {
+                key: key.into(),
+                vel: 0.into(),
+            })
+        };
+        let header = Header::default();
+        let tracks = vec![vec![
+            ev(0, note_on(60)),
+            ev(10, off(60)),
+            ev(0, note_on(62)),
+            ev(10, off(62)),
+            ev(0, END),
+        ]];
+        let options = WriteOptions {
+            note_off_as_note_on_zero: true,
+            ..WriteOptions::default()
+        };
+        let mut plain = Vec::new();
+        write_with_options(&header, &tracks, &WriteOptions::default(), &mut plain).unwrap();
+        let mut compact = Vec::new();
+        write_with_options(&header, &tracks, &options, &mut compact).unwrap();
+        // Every event after the first one can use running status
+        assert_eq!(compact.len() + 3, plain.len());
+        assert_eq!(
+            Smf::parse(&compact).unwrap().tracks[0],
+            vec![
+                ev(0, note_on(60)),
+                ev(10, on_zero(60)),
+                ev(0, note_on(62)),
+                ev(10, on_zero(62)),
+                ev(0, END),
+            ]
+        );
+    }
+
+    #[test]
+    fn iter_all() {
+        use crate::{Header, Smf};
+
+        let mut smf = Smf::new(Header::default());
+        smf.tracks = vec![
+            vec![ev(5, note_on(60)), ev(10, END)],
+            vec![],
+            vec![ev(0, note_on(62)), ev(3, END)],
+        ];
+        let tagged = smf
+            .iter_all()
+            .map(|(track, tick, ev)| (track, tick, ev.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tagged,
+            vec![
+                (0, 5, note_on(60)),
+                (0, 15, END),
+                (2, 0, note_on(62)),
+                (2, 3, END),
+            ]
+        );
+    }
+
+    #[test]
+    fn without_running_status() {
+        use crate::{write_with_options, Header, Smf, WriteOptions};
+
+        let header = Header::default();
+        let tracks = vec![vec![
+            ev(0, note_on(60)),
+            ev(10, note_on(62)),
+            ev(10, note_on(64)),
+            ev(0, END),
+        ]];
+        let options = WriteOptions {
+            running_status: false,
+            ..WriteOptions::default()
+        };
+        let mut plain = Vec::new();
+        write_with_options(&header, &tracks, &WriteOptions::default(), &mut plain).unwrap();
+        let mut expanded = Vec::new();
+        write_with_options(&header, &tracks, &options, &mut expanded).unwrap();
+        assert_eq!(expanded.len(), plain.len() + 2);
+        assert_eq!(
+            &expanded[22..34],
+            &[0, 0x90, 60, 64, 10, 0x90, 62, 64, 10, 0x90, 64, 64]
+        );
+        assert_eq!(Smf::parse(&expanded).unwrap().tracks, tracks);
+    }
+
+    #[test]
+    fn bend_offset() {
+        use crate::{num::u14, MidiMessage, PitchBend};
+
+        let bend = |raw: u16| MidiMessage::PitchBend {
+            bend: PitchBend(u14::new(raw)),
+        };
+        assert_eq!(bend(0x2000).bend_offset(100), bend(0x2064));
+        assert_eq!(bend(0x2000).bend_offset(-0x2000), bend(0));
+        assert_eq!(bend(0x100).bend_offset(-0x1000), bend(0));
+        assert_eq!(bend(0x3F00).bend_offset(i16::MAX), bend(0x3FFF));
+        assert_eq!(bend(0x1234).bend_offset(0), bend(0x1234));
+        let other = MidiMessage::ProgramChange { program: 5.into() };
+        assert_eq!(other.bend_offset(300), other);
+    }
+
+    #[test]
+    fn map_events() {
+        use crate::{Header, Smf};
+
+        let text = TrackEventKind::Meta(MetaMessage::Text(b"hi"));
+        let mut smf = Smf::new(Header::default());
+        smf.tracks = vec![
+            vec![
+                ev(0, note_on(60)),
+                ev(10, text),
+                ev(10, note_on(62)),
+                ev(0, END),
+            ],
+            vec![ev(5, text), ev(5, END)],
+        ];
+        smf.map_events(|ev| {
+            if let TrackEventKind::Midi { channel, .. } = &mut ev.kind {
+                *channel = 3.into();
+            }
+        });
+        let on = |key: u8| TrackEventKind::Midi {
+            channel: 3.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: 64.into(),
+            },
+        };
+        assert_eq!(smf.tracks[0][0], ev(0, on(60)));
+
+        smf.filter_map_events(|kind| match kind {
+            TrackEventKind::Meta(MetaMessage::Text(_)) => None,
+            TrackEventKind::Midi { .. } => Some(note_on(64)),
+            kind => Some(kind),
+        });
+        assert_eq!(
+            smf.tracks,
+            vec![
+                vec![ev(0, note_on(64)), ev(20, note_on(64)), ev(0, END)],
+                vec![ev(10, END)],
+            ]
+        );
+    }
+
+    #[test]
+    fn borrow_tracks() {
+        use crate::{Header, Smf};
+
+        let mut smf = Smf::new(Header::default());
+        smf.tracks.push(vec![ev(0, note_on(60)), ev(0, END)]);
+        smf.tracks.push(edit::empty_track());
+        assert_eq!(smf.tracks().len(), 2);
+        assert_eq!(smf.track(0), Some(&[ev(0, note_on(60)), ev(0, END)][..]));
+        assert_eq!(smf.track(1).map(<[_]>::len), Some(1));
+        assert_eq!(smf.track(2), None);
+    }
+
+    #[test]
+    fn smf_parts() {
+        use crate::{Format, Smf};
+
+        let smf = Smf::empty(Format::Parallel, 96);
+        let (header, tracks) = smf.clone().into_parts();
+        assert_eq!(header, smf.header);
+        assert_eq!(tracks, vec![vec![ev(0, END)]]);
+        assert_eq!(Smf::from_parts(header, tracks), smf);
+    }
+
+    #[test]
+    fn insert_remove_tracks() {
+        use crate::{Format, Smf};
+
+        let mut smf = Smf::empty(Format::SingleTrack, 96);
+        let track = vec![ev(0, note_on(60)), ev(10, END)];
+        assert!(smf.insert_track(1, track.clone()).is_err());
+        assert_eq!(smf.tracks.len(), 1);
+        smf.header.format = Format::Parallel;
+        assert!(smf.insert_track(2, track.clone()).is_err());
+        smf.insert_track(0, track.clone()).unwrap();
+        assert_eq!(smf.tracks[0], track);
+        assert_eq!(smf.tracks.len(), 2);
+        assert_eq!(smf.remove_track(0), Some(track));
+        assert_eq!(smf.remove_track(1), None);
+        assert_eq!(smf.tracks, vec![vec![ev(0, END)]]);
+
+        let mut out = Vec::new();
+        smf.insert_track(1, vec![ev(0, END)]).unwrap();
+        smf.write(&mut out).unwrap();
+        assert_eq!(Smf::parse(&out).unwrap().tracks.len(), 2);
+    }
+
+    #[test]
+    fn export_track() {
+        use crate::{Format, Header, Smf, Timing};
+
+        let tempo = TrackEventKind::Meta(MetaMessage::Tempo(400_000.into()));
+        let signature = TrackEventKind::Meta(MetaMessage::TimeSignature(3, 2, 24, 8));
+        let name = TrackEventKind::Meta(MetaMessage::TrackName(b"conductor"));
+        let mut smf = Smf::new(Header::new(Format::Parallel, Timing::Metrical(96.into())));
+        smf.tracks = vec![
+            vec![ev(0, name), ev(0, signature), ev(20, tempo), ev(0, END)],
+            vec![ev(10, note_on(60)), ev(20, note_on(62)), ev(0, END)],
+        ];
+        let exported = smf.export_track(1).unwrap();
+        assert_eq!(exported.header.format, Format::SingleTrack);
+        assert_eq!(exported.header.timing, smf.header.timing);
+        assert_eq!(
+            exported.tracks,
+            vec![vec![
+                ev(0, signature),
+                ev(10, note_on(60)),
+                ev(10, tempo),
+                ev(10, note_on(62)),
+                ev(0, END),
+            ]]
+        );
+
+        // Without a conductor track, defaults are inserted
+        smf.header.format = Format::Sequential;
+        let exported = smf.export_track(1).unwrap();
+        assert_eq!(
+            exported.tracks[0][..2],
+            [
+                ev(0, TrackEventKind::Meta(MetaMessage::Tempo(500_000.into()))),
+                ev(
+                    0,
+                    TrackEventKind::Meta(MetaMessage::TimeSignature(4, 2, 24, 8))
+                ),
+            ]
+        );
+        assert_eq!(exported.tracks[0][2..], smf.tracks[1][..]);
+        assert!(smf.export_track(2).is_none());
+    }
+
+    #[test]
+    fn dump() {
+        use crate::{Format, Fps, Header, PitchBend, Smf, SmpteTime, Timing};
+
+        let midi = |message| TrackEventKind::Midi {
+            channel: 3.into(),
+            message,
+        };
+        let meta = TrackEventKind::Meta;
+        let mut smf = Smf::new(Header::new(Format::Parallel, Timing::Metrical(96.into())));
+        smf.tracks = vec![
+            vec![
+                ev(0, meta(MetaMessage::TrackNumber(None))),
+                ev(0, meta(MetaMessage::TrackName(b"Say \"hi\"\\\xE9"))),
+                ev(0, meta(MetaMessage::Tempo(500_000.into()))),
+                ev(0, meta(MetaMessage::TimeSignature(6, 3, 24, 8))),
+                ev(0, meta(MetaMessage::KeySignature(-3, true))),
+                ev(
+                    0,
+                    meta(MetaMessage::SmpteOffset(
+                        SmpteTime::new(1, 2, 3, 4, 5, Fps::Fps25).unwrap(),
+                    )),
+                ),
+                ev(0, meta(MetaMessage::Unknown(0x60, &[1, 0xAB]))),
+                ev(10, END),
+            ],
+            vec![
+                ev(0, note_on(60)),
+                ev(
+                    5,
+                    midi(MidiMessage::Controller {
+                        controller: 64.into(),
+                        value: 127.into(),
+                    }),
+                ),
+                ev(
+                    0,
+                    midi(MidiMessage::PitchBend {
+                        bend: PitchBend(0x2000.into()),
+                    }),
+                ),
+                ev(0, midi(MidiMessage::ProgramChange { program: 9.into() })),
+                ev(1, TrackEventKind::SysEx(&[0x7E, 0x7F, 0xF7])),
+                ev(0, TrackEventKind::Escape(&[])),
+                ev(0, END),
+            ],
+        ];
+        let mut text = String::new();
+        smf.dump(&mut text).unwrap();
+        assert_eq!(
+            text,
+            "header parallel tpq 96\n\
+             track 0\n  \
+             0 track_number\n  \
+             0 track_name \"Say \\\"hi\\\"\\\\\\xE9\"\n  \
+             0 tempo 500000\n  \
+             0 time_signature 6 3 24 8\n  \
+             0 key_signature -3 minor\n  \
+             0 smpte_offset 25 1 2 3 4 5\n  \
+             0 meta 96 01 AB\n  \
+             10 end_of_track\n\
+             track 1\n  \
+             0 note_on 0 60 64\n  \
+             5 controller 3 64 127\n  \
+             5 pitch_bend 3 8192\n  \
+             5 program_change 3 9\n  \
+             6 sysex 7E 7F F7\n  \
+             6 escape\n  \
+             6 end_of_track\n"
+        );
+    }
+
+    #[test]
+    fn from_dump() {
+        use crate::{Arena, Smf};
+
+        let arena = Arena::new();
+        let raw = include_bytes!("../test-asset/Clementi.mid");
+        let smf = Smf::parse(raw).unwrap();
+        let mut text = String::new();
+        smf.dump(&mut text).unwrap();
+        let parsed = Smf::from_dump(&text, &arena).unwrap();
+        assert_eq!(parsed, smf);
+        let mut again = String::new();
+        parsed.dump(&mut again).unwrap();
+        assert_eq!(again, text);
+
+        let text = "header  single timecode 25 40\n\n\
+                    track 0\n\
+                    0 text \"two  words \\\" \\x00\"\n\
+                    0 track_number\n\
+                    3 key_signature -2 major\n\
+                    3 sysex\n\
+                    4   end_of_track  \n";
+        let smf = Smf::from_dump(text, &arena).unwrap();
+        assert_eq!(
+            smf.tracks,
+            vec![vec![
+                ev(
+                    0,
+                    TrackEventKind::Meta(MetaMessage::Text(b"two  words \" \0"))
+                ),
+                ev(0, TrackEventKind::Meta(MetaMessage::TrackNumber(None))),
+                ev(
+                    3,
+                    TrackEventKind::Meta(MetaMessage::KeySignature(-2, false))
+                ),
+                ev(0, TrackEventKind::SysEx(&[])),
+                ev(1, END),
+            ]]
+        );
+
+        for bad in [
+            "",
+            "track 0",
+            "header single tpq 96\n0 end_of_track",
+            "header single tpq 96\ntrack 1",
+            "header single tpq 96\ntrack 0\n5 end_of_track\n4 end_of_track",
+            "header single tpq 96\ntrack 0\n0 note_on 16 60 64",
+            "header single tpq 96\ntrack 0\n0 note_on 0 60",
+            "header single tpq 96\ntrack 0\n0 note_on 0 60 64 1",
+            "header single tpq 96\ntrack 0\n0 text unquoted",
+            "header single tpq 96\ntrack 0\n0 text \"\\q\"",
+            "header single tpq 96\ntrack 0\n0 sysex GG",
+            "header single tpq 96\ntrack 0\n0 unknown_event",
+        ]
+        .iter()
+        {
+            assert!(Smf::from_dump(bad, &arena).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn track_number() {
+        use crate::{Format, Header, Smf, Timing};
+
+        let number = |num| TrackEventKind::Meta(MetaMessage::TrackNumber(num));
+        let mut smf = Smf::new(Header::new(Format::Sequential, Timing::Metrical(96.into())));
+        smf.tracks = vec![
+            vec![ev(0, number(Some(7))), ev(0, END)],
+            vec![ev(0, number(None)), ev(0, END)],
+            edit::empty_track(),
+            vec![ev(5, number(Some(9))), ev(0, END)],
+        ];
+        assert_eq!(smf.track_number(0), Some(7));
+        assert_eq!(smf.track_number(1), Some(1));
+        assert_eq!(smf.track_number(2), Some(2));
+        // Only a `TrackNumber` at the very start of the track counts
+        assert_eq!(smf.track_number(3), Some(3));
+        assert_eq!(smf.track_number(4), None);
+    }
+
+    #[test]
+    fn merge_keeps_simultaneous_order() {
+        let program = TrackEventKind::Midi {
+            channel: 0.into(),
+            message: MidiMessage::ProgramChange { program: 5.into() },
+        };
+        let tempo = TrackEventKind::Meta(MetaMessage::Tempo(500_000.into()));
+        let conductor = vec![ev(0, tempo), ev(0, note_on(40)), ev(20, END)];
+        let piano = vec![
+            ev(0, program),
+            ev(0, note_on(60)),
+            ev(5, note_on(62)),
+            ev(0, END),
+        ];
+        let merged = edit::merge(&[conductor, piano]);
+        assert_eq!(
+            merged,
+            vec![
+                ev(0, tempo),
+                ev(0, note_on(40)),
+                ev(0, program),
+                ev(0, note_on(60)),
+                ev(5, note_on(62)),
+                ev(15, END),
+            ]
+        );
+    }
+
+    #[test]
+    fn timed_event_order() {
+        use crate::edit::TimedEvent;
+        use std::{cmp::Reverse, collections::BinaryHeap};
+
+        let timed = |tick, seq, event| TimedEvent { tick, seq, event };
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse(timed(10, 0, note_on(62))));
+        heap.push(Reverse(timed(5, 1, note_on(61))));
+        heap.push(Reverse(timed(5, 0, note_on(60))));
+        let popped = std::iter::from_fn(|| heap.pop())
+            .map(|Reverse(ev)| (ev.tick, ev.seq, ev.event))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            popped,
+            vec![
+                (5, 0, note_on(60)),
+                (5, 1, note_on(61)),
+                (10, 0, note_on(62))
+            ]
+        );
+        assert_eq!(timed(3, 2, note_on(60)), timed(3, 2, END));
+    }
+
+    #[test]
+    fn make_monophonic() {
+        let msg = |channel: u8, message| TrackEventKind::Midi {
+            channel: channel.into(),
+            message,
+        };
+        let off = |key: u8| {
+            msg(
+                0,
+                MidiMessage::NoteOff {
+                    key: key.into(),
+                    vel: 64.into(),
+                },
+            )
+        };
+        let other = msg(
+            1,
+            MidiMessage::NoteOn {
+                key: 61.into(),
+                vel: 64.into(),
+            },
+        );
+        let mut track = vec![
+            ev(0, note_on(60)),
+            ev(10, note_on(62)),
+            ev(10, off(60)),
+            ev(10, off(62)),
+            ev(10, note_on(64)),
+            ev(5, other),
+            ev(5, END),
+        ];
+        edit::make_monophonic(&mut track, 0.into());
+        assert_eq!(
+            track,
+            vec![
+                ev(0, note_on(60)),
+                ev(10, off(60)),
+                ev(0, note_on(62)),
+                ev(20, off(62)),
+                ev(10, note_on(64)),
+                ev(5, other),
+                ev(5, off(64)),
+                ev(0, END),
+            ]
+        );
+
+        // Other channels are left alone
+        let mut poly = vec![ev(0, note_on(60)), ev(10, note_on(62)), ev(10, END)];
+        let expected = poly.clone();
+        edit::make_monophonic(&mut poly, 1.into());
+        assert_eq!(poly, expected);
+    }
+
+    #[test]
+    fn quantize_durations() {
+        let off = |key: u8| TrackEventKind::Midi {
+            channel: 0.into(),
+            message: MidiMessage::NoteOff {
+                key: key.into(),
+                vel: 0.into(),
+            },
+        };
+        let track = vec![
+            ev(0, note_on(60)),
+            ev(13, off(60)),
+            ev(7, note_on(62)),
+            ev(4, off(62)),
+            ev(3, note_on(62)),
+            ev(17, off(62)),
+            ev(1, END),
+        ];
+        let mut quantized = track.clone();
+        edit::quantize_durations(&mut quantized, 10).unwrap();
+        assert_eq!(
+            quantized,
+            vec![
+                ev(0, note_on(60)),
+                ev(10, off(60)),
+                ev(10, note_on(62)),
+                // Lengthened, but stopped short by the next start of the same key
+                ev(7, off(62)),
+                ev(0, note_on(62)),
+                // Stopped short by the end of the track
+                ev(18, off(62)),
+                ev(0, END),
+            ]
+        );
+
+        let mut unchanged = track.clone();
+        edit::quantize_durations(&mut unchanged, 0).unwrap();
+        assert_eq!(unchanged, track);
+    }
+
+    #[test]
+    fn humanize() {
+        let note = |key: u8, vel: u8| TrackEventKind::Midi {
+            channel: 0.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: vel.into(),
+            },
+        };
+        let track = vec![
+            ev(0, note(60, 100)),
+            ev(100, note(60, 0)),
+            ev(0, note(62, 126)),
+            ev(100, note(62, 0)),
+            ev(100, END),
+        ];
+
+        let mut unchanged = track.clone();
+        edit::humanize(&mut unchanged, 0, 0, 42).unwrap();
+        assert_eq!(unchanged, track);
+
+        let mut a = track.clone();
+        let mut b = track.clone();
+        edit::humanize(&mut a, 10, 5, 7).unwrap();
+        edit::humanize(&mut b, 10, 5, 7).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.last().map(|ev| ev.kind), Some(END));
+        let mut tick = 0;
+        let mut starts = Vec::new();
+        for ev in a.iter() {
+            tick += ev.delta.as_int();
+            if let TrackEventKind::Midi {
+                message: MidiMessage::NoteOn { key, vel },
+                ..
+            } = ev.kind
+            {
+                if vel > 0 {
+                    let base = if key == 60 { 100 } else { 126 };
+                    assert!(vel.as_int() + 5 >= base && vel.as_int() <= base + 5);
+                    starts.push((key, tick));
+                } else {
+                    let start = starts.iter().find(|&&(k, _)| k == key).unwrap().1;
+                    assert_eq!(tick - start, 100);
+                }
+            }
+        }
+        assert_eq!(tick, 300);
+    }
+
+    #[test]
+    fn freeze() {
+        use crate::PitchBend;
+
+        let midi = |channel: u8, message| TrackEventKind::Midi {
+            channel: channel.into(),
+            message,
+        };
+        let cc = |channel: u8, controller: u8, value: u8| {
+            midi(
+                channel,
+                MidiMessage::Controller {
+                    controller: controller.into(),
+                    value: value.into(),
+                },
+            )
+        };
+        let program = |channel: u8, program: u8| {
+            midi(
+                channel,
+                MidiMessage::ProgramChange {
+                    program: program.into(),
+                },
+            )
+        };
+        let bend = |channel: u8, raw: u16| {
+            midi(
+                channel,
+                MidiMessage::PitchBend {
+                    bend: PitchBend(raw.into()),
+                },
+            )
+        };
+        let track = [
+            ev(0, cc(0, 7, 100)),
+            ev(0, program(0, 5)),
+            ev(0, cc(0, 0, 1)),
+            ev(0, program(0, 6)),
+            ev(0, cc(0, 101, 0)),
+            ev(0, cc(0, 100, 0)),
+            ev(0, cc(0, 6, 12)),
+            ev(0, cc(0, 99, 1)),
+            ev(0, cc(0, 98, 2)),
+            ev(0, cc(0, 101, 0)),
+            ev(10, bend(0, 0x3000)),
+            ev(0, cc(1, 10, 20)),
+            ev(0, bend(1, 0x1000)),
+            ev(0, cc(1, 121, 0)),
+            ev(0, cc(1, 64, 127)),
+            ev(0, cc(0, 123, 0)),
+            ev(10, cc(2, 7, 50)),
+            ev(0, END),
+        ];
+        assert_eq!(
+            edit::freeze(&track, 20),
+            vec![
+                cc(0, 0, 1),
+                program(0, 6),
+                cc(0, 7, 100),
+                cc(0, 101, 0),
+                cc(0, 100, 0),
+                cc(0, 6, 12),
+                cc(0, 101, 0),
+                bend(0, 0x3000),
+                cc(1, 64, 127),
+            ]
+        );
+        assert_eq!(edit::freeze(&track, 10).len(), 7);
+        assert_eq!(edit::freeze(&track, 0), vec![]);
+        assert_eq!(edit::freeze(&track, 100).last(), Some(&cc(2, 7, 50)));
+
+        // Deselecting the parameter with the null RPN keeps its value
+        let track = [
+            ev(0, cc(0, 101, 0)),
+            ev(0, cc(0, 100, 0)),
+            ev(0, cc(0, 6, 12)),
+            ev(0, cc(0, 101, 127)),
+            ev(0, cc(0, 100, 127)),
+            ev(10, END),
+        ];
+        assert_eq!(
+            edit::freeze(&track, 10),
+            vec![
+                cc(0, 101, 0),
+                cc(0, 100, 0),
+                cc(0, 6, 12),
+                cc(0, 101, 127),
+                cc(0, 100, 127),
+            ]
+        );
+
+        // Each parameter keeps its own value
+        let track = [
+            ev(0, cc(0, 101, 0)),
+            ev(0, cc(0, 100, 0)),
+            ev(0, cc(0, 6, 2)),
+            ev(0, cc(0, 38, 50)),
+            ev(0, cc(0, 99, 1)),
+            ev(0, cc(0, 98, 2)),
+            ev(0, cc(0, 6, 5)),
+            ev(0, cc(0, 101, 0)),
+            ev(0, cc(0, 100, 0)),
+            ev(10, END),
+        ];
+        assert_eq!(
+            edit::freeze(&track, 10),
+            vec![
+                cc(0, 101, 0),
+                cc(0, 100, 0),
+                cc(0, 6, 2),
+                cc(0, 38, 50),
+                cc(0, 99, 1),
+                cc(0, 98, 2),
+                cc(0, 6, 5),
+                cc(0, 101, 0),
+                cc(0, 100, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn scale_tempo() {
+        let tempo = |t: u32| TrackEventKind::Meta(MetaMessage::Tempo(t.into()));
+        let mut track = vec![
+            ev(0, tempo(500_000)),
+            ev(10, note_on(60)),
+            ev(10, tempo(0xF0_0000)),
+            ev(10, END),
+        ];
+        edit::scale_tempo(&mut track, 1.25);
+        assert_eq!(
+            track,
+            vec![
+                ev(0, tempo(625_000)),
+                ev(10, note_on(60)),
+                ev(10, tempo(0xFF_FFFF)),
+                ev(10, END),
+            ]
+        );
+        edit::scale_tempo(&mut track, 0.0);
+        assert_eq!(track[0], ev(0, tempo(1)));
+
+        let mut track = vec![ev(10, note_on(60)), ev(10, END)];
+        edit::scale_tempo(&mut track, 0.5);
+        assert_eq!(
+            track,
+            vec![ev(0, tempo(250_000)), ev(10, note_on(60)), ev(10, END)]
+        );
+    }
+
+    #[test]
+    fn extract_channel() {
+        let on = |channel: u8, key: u8| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: 64.into(),
+            },
+        };
+        let tempo = TrackEventKind::Meta(MetaMessage::Tempo(500_000.into()));
+        let name = TrackEventKind::Meta(MetaMessage::TrackName(b"mix"));
+        let track = vec![
+            ev(0, name),
+            ev(0, tempo),
+            ev(4, on(1, 60)),
+            ev(4, on(2, 61)),
+            ev(4, TrackEventKind::SysEx(&[0x7E, 0xF7])),
+            ev(4, on(2, 62)),
+            ev(4, on(1, 63)),
+            ev(4, END),
+        ];
+        assert_eq!(
+            edit::extract_channel(&track, 1.into()),
+            vec![
+                ev(0, tempo),
+                ev(4, on(1, 60)),
+                ev(16, on(1, 63)),
+                ev(4, END)
+            ]
+        );
+        assert_eq!(
+            edit::extract_channel(&track, 2.into()),
+            vec![ev(0, tempo), ev(8, on(2, 61)), ev(8, on(2, 62)), ev(8, END)]
+        );
+    }
+
+    #[test]
+    fn sort_track() {
+        let track = edit::sort_track(vec![
+            (30, END),
+            (10, note_on(62)),
+            (0, note_on(60)),
+            (10, note_on(61)),
+        ])
+        .unwrap();
+        assert_eq!(
+            track,
+            vec![
+                ev(0, note_on(60)),
+                ev(10, note_on(62)),
+                ev(0, note_on(61)),
+                ev(20, END),
+            ]
+        );
+        assert!(edit::sort_track(vec![(0, note_on(60)), (1 << 28, END)]).is_err());
+        assert!(edit::sort_track(vec![(0, note_on(60)), ((1 << 28) - 1, END)]).is_ok());
+    }
+
+    #[test]
+    fn absolute_events_roundtrip() {
+        let track = vec![
+            ev(0, note_on(60)),
+            ev(10, note_on(62)),
+            ev(0, note_on(61)),
+            ev(20, END),
+        ];
+        let events = edit::absolute_events(&track).collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (0, note_on(60)),
+                (10, note_on(62)),
+                (10, note_on(61)),
+                (30, END)
+            ]
+        );
+        // Shift every event but the first one, then rebuild the track
+        let shifted = events
+            .into_iter()
+            .enumerate()
+            .map(|(i, (tick, kind))| (if i == 0 { tick } else { tick + 5 }, kind));
+        assert_eq!(
+            edit::sort_track(shifted).unwrap(),
+            vec![
+                ev(0, note_on(60)),
+                ev(15, note_on(62)),
+                ev(0, note_on(61)),
+                ev(20, END)
+            ]
+        );
+    }
+
+    #[test]
+    fn classify() {
+        use crate::{FileClass, Header, Smf};
+
+        let note = |channel: u8, key: u8, on: bool| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: if on { 64 } else { 0 }.into(),
+            },
+        };
+        let smf = |tracks: Vec<Vec<TrackEvent<'static>>>| Smf {
+            header: Header::default(),
+            tracks,
+        };
+        assert_eq!(smf(vec![edit::empty_track()]).classify(), FileClass::Empty);
+
+        let drums = (0..16)
+            .flat_map(|i| {
+                vec![
+                    ev(0, note(9, 36 + i % 2, true)),
+                    ev(10, note(9, 36 + i % 2, false)),
+                ]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(smf(vec![drums.clone()]).classify(), FileClass::DrumLoop);
+
+        let melody = vec![
+            ev(0, note(0, 60, true)),
+            ev(10, note(0, 60, false)),
+            ev(0, note(0, 62, true)),
+            ev(10, note(0, 62, false)),
+            ev(0, END),
+        ];
+        assert_eq!(
+            smf(vec![melody.clone()]).classify(),
+            FileClass::SingleMelody
+        );
+        let chord = vec![
+            ev(0, note(0, 60, true)),
+            ev(0, note(0, 64, true)),
+            ev(10, note(0, 60, false)),
+            ev(0, note(0, 64, false)),
+        ];
+        assert_eq!(smf(vec![chord]).classify(), FileClass::SingleInstrument);
+        assert_eq!(smf(vec![melody, drums]).classify(), FileClass::Multitrack);
+
+        // Any channel can be set up as a GS rhythm part
+        let part_2 =
+            TrackEventKind::SysEx(&[0x41, 0x10, 0x42, 0x12, 0x40, 0x12, 0x15, 0x01, 0x18, 0xF7]);
+        let mut gs_drums = vec![ev(0, part_2)];
+        gs_drums.extend((0..16).flat_map(|i| {
+            vec![
+                ev(0, note(1, 36 + i % 2, true)),
+                ev(10, note(1, 36 + i % 2, false)),
+            ]
+        }));
+        assert_eq!(smf(vec![gs_drums]).classify(), FileClass::DrumLoop);
+    }
+
+    #[test]
+    fn leading_silence() {
+        use crate::{Header, Smf};
+
+        let tempo = TrackEventKind::Meta(MetaMessage::Tempo(500_000.into()));
+        let silent = TrackEventKind::Midi {
+            channel: 0.into(),
+            message: MidiMessage::NoteOn {
+                key: 10.into(),
+                vel: 0.into(),
+            },
+        };
+        let mut smf = Smf {
+            header: Header::default(),
+            tracks: vec![
+                vec![ev(0, tempo), ev(30, tempo), ev(50, END)],
+                vec![ev(5, silent), ev(35, note_on(60)), ev(10, END)],
+                vec![ev(60, note_on(62)), ev(0, END)],
+            ],
+        };
+        assert_eq!(smf.first_note_tick(), Some(40));
+        smf.trim_leading_silence();
+        assert_eq!(
+            smf.tracks,
+            vec![
+                vec![ev(0, tempo), ev(0, tempo), ev(40, END)],
+                vec![ev(0, silent), ev(0, note_on(60)), ev(10, END)],
+                vec![ev(20, note_on(62)), ev(0, END)],
+            ]
+        );
+        assert_eq!(smf.first_note_tick(), Some(0));
+
+        let mut empty = Smf {
+            header: Header::default(),
+            tracks: vec![vec![ev(0, silent), ev(10, END)]],
+        };
+        assert_eq!(empty.first_note_tick(), None);
+        empty.trim_leading_silence();
+        assert_eq!(empty.tracks[0][1], ev(10, END));
+    }
+
+    #[test]
+    fn normalize_format() {
+        use crate::{Format, Header, Smf, Timing};
+
+        let tempo = TrackEventKind::Meta(MetaMessage::Tempo(500_000.into()));
+        let note = |channel: u8, key: u8| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: 64.into(),
+            },
+        };
+        let mut smf = Smf::new(Header::new(Format::Sequential, Timing::Metrical(96.into())));
+        // A conductor track followed by two parts
+        smf.tracks = vec![
+            vec![ev(0, tempo), ev(0, END)],
+            vec![ev(0, note(0, 60)), ev(0, END)],
+            vec![ev(0, note(1, 60)), ev(0, END)],
+        ];
+        assert!(!smf.is_truly_sequential());
+        assert!(smf.normalize_format());
+        assert_eq!(smf.header.format, Format::Parallel);
+        assert!(!smf.normalize_format());
+
+        // Two patterns on the same channel
+        smf.tracks = vec![
+            vec![ev(0, tempo), ev(0, note(0, 60)), ev(0, END)],
+            vec![ev(0, tempo), ev(0, note(0, 62)), ev(0, END)],
+        ];
+        assert!(smf.is_truly_sequential());
+        assert!(!smf.normalize_format());
+        smf.header.format = Format::SingleTrack;
+        assert!(smf.normalize_format());
+        assert_eq!(smf.header.format, Format::Sequential);
+        smf.tracks[1][1] = ev(0, note(1, 62));
+        assert!(!smf.is_truly_sequential());
+    }
+
+    #[test]
+    fn conductor_tempo_map() {
+        use crate::{Format, Header, Smf, Timing};
+
+        let tempo = |t: u32| TrackEventKind::Meta(MetaMessage::Tempo(t.into()));
+        let mut smf = Smf::new(Header::new(Format::Parallel, Timing::Metrical(96.into())));
+        smf.tracks = vec![
+            vec![ev(0, tempo(500_000)), ev(96, tempo(400_000)), ev(0, END)],
+            vec![ev(48, tempo(300_000)), ev(0, note_on(60)), ev(0, END)],
+        ];
+        assert_eq!(smf.conductor_track(), Some(&smf.tracks[0]));
+        assert_eq!(
+            smf.global_tempo_map(),
+            vec![(0, 500_000.into()), (96, 400_000.into())]
+        );
+        assert_eq!(smf.tempo_changes(), vec![(0, 120.0), (96, 150.0)]);
+
+        // Without tempo changes in the conductor track, all tracks are scanned
+        smf.tracks[0] = vec![ev(10, tempo(600_000)), ev(0, END)];
+        smf.tracks.swap(0, 1);
+        assert_eq!(smf.global_tempo_map(), vec![(48, 300_000.into())]);
+        smf.tracks[0] = vec![ev(0, note_on(60)), ev(0, END)];
+        assert_eq!(smf.global_tempo_map(), vec![(10, 600_000.into())]);
+
+        smf.header.format = Format::Sequential;
+        assert_eq!(smf.conductor_track(), None);
+    }
+
+    #[test]
+    fn conductor_time_signature_map() {
+        use crate::{Format, Header, Smf, Timing};
+
+        let sig =
+            |num: u8, den: u8| TrackEventKind::Meta(MetaMessage::TimeSignature(num, den, 24, 8));
+        let tempo = TrackEventKind::Meta(MetaMessage::Tempo(500_000.into()));
+        let mut smf = Smf::new(Header::new(Format::Parallel, Timing::Metrical(96.into())));
+        smf.tracks = vec![
+            vec![ev(0, sig(4, 2)), ev(384, sig(6, 3)), ev(0, END)],
+            vec![ev(0, sig(3, 2)), ev(0, note_on(60)), ev(0, END)],
+        ];
+        assert_eq!(
+            smf.global_time_signature_map(),
+            vec![(0, (4, 2, 24, 8)), (384, (6, 3, 24, 8))]
+        );
+
+        // Without time signatures in the conductor track, all tracks are scanned
+        smf.tracks[0] = vec![ev(0, tempo), ev(96, sig(2, 2)), ev(0, END)];
+        smf.tracks.swap(0, 1);
+        assert_eq!(smf.global_time_signature_map(), vec![(0, (3, 2, 24, 8))]);
+        smf.tracks[0] = vec![ev(0, note_on(60)), ev(0, END)];
+        assert_eq!(smf.global_time_signature_map(), vec![(96, (2, 2, 24, 8))]);
+        assert_eq!(smf.global_tempo_map(), vec![(0, 500_000.into())]);
+
+        // Simultaneous changes are sorted by track order
+        smf.header.format = Format::Sequential;
+        smf.tracks = vec![
+            vec![ev(96, sig(5, 2)), ev(0, END)],
+            vec![ev(0, sig(4, 2)), ev(96, sig(7, 3)), ev(0, END)],
+        ];
+        assert_eq!(
+            smf.global_time_signature_map(),
+            vec![(0, (4, 2, 24, 8)), (96, (5, 2, 24, 8)), (96, (7, 3, 24, 8))]
+        );
+    }
+
+    #[test]
+    fn estimate_key() {
+        use crate::{Header, Smf};
+
+        let notes = |channel: u8, keys: &[u8]| {
+            keys.iter()
+                .map(|&key| {
+                    ev(
+                        10,
+                        TrackEventKind::Midi {
+                            channel: channel.into(),
+                            message: MidiMessage::NoteOn {
+                                key: key.into(),
+                                vel: 64.into(),
+                            },
+                        },
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut smf = Smf::new(Header::default());
+        // A few bars over the C major scale, stressing the tonic triad
+        smf.tracks = vec![notes(
+            0,
+            &[60, 62, 64, 65, 67, 69, 71, 72, 60, 64, 67, 60, 67],
+        )];
+        assert_eq!(smf.estimate_key(), Some((0, false)));
+        // The same, but over A minor
+        smf.tracks = vec![notes(
+            0,
+            &[57, 59, 60, 62, 64, 65, 68, 69, 57, 60, 64, 57, 64],
+        )];
+        assert_eq!(smf.estimate_key(), Some((0, true)));
+        // G major has one sharp, E flat major three flats
+        smf.tracks = vec![notes(
+            0,
+            &[67, 69, 71, 72, 74, 76, 78, 79, 67, 71, 74, 67, 74],
+        )];
+        assert_eq!(smf.estimate_key(), Some((1, false)));
+        smf.tracks = vec![notes(
+            0,
+            &[63, 65, 67, 68, 70, 72, 74, 75, 63, 67, 70, 63, 70],
+        )];
+        assert_eq!(smf.estimate_key(), Some((-3, false)));
+
+        // An explicit key signature wins, even in a later track
+        let signature = TrackEventKind::Meta(MetaMessage::KeySignature(-2, true));
+        smf.tracks.push(vec![ev(0, signature), ev(0, END)]);
+        assert_eq!(smf.estimate_key(), Some((-2, true)));
+
+        // Drums have no key, even on a channel set up as a GS rhythm part
+        smf.tracks = vec![notes(9, &[36, 38, 42, 36])];
+        assert_eq!(smf.estimate_key(), None);
+        let part_11 =
+            TrackEventKind::SysEx(&[0x41, 0x10, 0x42, 0x12, 0x40, 0x1A, 0x15, 0x01, 0x00, 0xF7]);
+        smf.tracks[0].insert(0, ev(0, part_11));
+        smf.tracks[0].extend(notes(10, &[36, 38, 42, 36]));
+        assert_eq!(smf.drum_channels(), 1 << 9 | 1 << 10);
+        assert_eq!(smf.estimate_key(), None);
+    }
+
+    #[test]
+    fn drum_channels() {
+        use crate::{Header, Smf};
+
+        let on = |channel: u8| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::NoteOn {
+                key: 36.into(),
+                vel: 100.into(),
+            },
+        };
+        let bank = |channel: u8, value: u8| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::Controller {
+                controller: 0.into(),
+                value: value.into(),
+            },
+        };
+        let program = |channel: u8| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::ProgramChange { program: 0.into() },
+        };
+        // Rhythm part setup for parts 11 and 10 (channels 10 and 9, counting from 0)
+        let part_11 =
+            TrackEventKind::SysEx(&[0x41, 0x10, 0x42, 0x12, 0x40, 0x1A, 0x15, 0x01, 0x00, 0xF7]);
+        let part_10_off =
+            TrackEventKind::SysEx(&[0x41, 0x10, 0x42, 0x12, 0x40, 0x10, 0x15, 0x00, 0x1B, 0xF7]);
+        let gs_reset =
+            TrackEventKind::SysEx(&[0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7]);
+
+        let mut smf = Smf::new(Header::default());
+        smf.tracks = vec![vec![
+            ev(0, part_11),
+            ev(0, bank(3, 0x78)),
+            ev(0, program(3)),
+            ev(0, bank(5, 0x78)),
+            ev(10, on(9)),
+            ev(0, on(10)),
+            ev(0, on(3)),
+            ev(0, on(5)),
+            ev(0, bank(3, 0x79)),
+            ev(0, END),
+        ]];
+        // The bank select on channel 5 is never followed by a program change
+        assert_eq!(smf.drum_channels(), 1 << 9 | 1 << 10 | 1 << 3);
+        smf.tracks[0].insert(4, ev(0, program(5)));
+        assert_eq!(smf.drum_channels(), 1 << 9 | 1 << 10 | 1 << 3 | 1 << 5);
+
+        smf.tracks = vec![
+            vec![ev(0, part_10_off), ev(10, gs_reset), ev(0, END)],
+            vec![ev(5, on(9)), ev(0, on(10)), ev(10, on(9)), ev(0, END)],
+        ];
+        assert_eq!(smf.drum_channels(), 1 << 9);
+
+        smf.tracks = vec![vec![ev(0, on(0)), ev(0, END)]];
+        assert_eq!(smf.drum_channels(), 0);
+    }
+
+    #[test]
+    fn apply_velocity_curve() {
+        let note = |vel: u8| TrackEventKind::Midi {
+            channel: 0.into(),
+            message: MidiMessage::NoteOn {
+                key: 60.into(),
+                vel: vel.into(),
+            },
+        };
+        // Halve every velocity, with some out of range entries
+        let mut lut = [0; 128];
+        for (vel, entry) in lut.iter_mut().enumerate() {
+            *entry = vel as u8 / 2;
+        }
+        lut[100] = 200;
+        let mut track = vec![
+            ev(0, note(100)),
+            ev(0, note(64)),
+            ev(0, note(1)),
+            ev(0, note(0)),
+            ev(0, END),
+        ];
+        edit::apply_velocity_curve(&mut track, &lut);
+        assert_eq!(
+            track,
+            vec![
+                ev(0, note(127)),
+                ev(0, note(32)),
+                ev(0, note(1)),
+                ev(0, note(0)),
+                ev(0, END),
+            ]
+        );
+    }
+
+    #[test]
+    fn dedup_controllers() {
+        let cc = |channel: u8, controller: u8, value: u8| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::Controller {
+                controller: controller.into(),
+                value: value.into(),
+            },
+        };
+        let mut track = vec![
+            ev(0, cc(0, 11, 100)),
+            ev(5, cc(0, 11, 100)),
+            ev(5, cc(1, 11, 100)),
+            ev(5, cc(0, 11, 90)),
+            ev(5, cc(0, 6, 2)),
+            ev(5, cc(0, 6, 2)),
+            ev(5, cc(0, 121, 0)),
+            ev(5, cc(0, 11, 90)),
+            ev(5, cc(0, 11, 90)),
+            ev(5, END),
+        ];
+        edit::dedup_controllers(&mut track);
+        assert_eq!(
+            track,
+            vec![
+                ev(0, cc(0, 11, 100)),
+                ev(10, cc(1, 11, 100)),
+                ev(5, cc(0, 11, 90)),
+                ev(5, cc(0, 6, 2)),
+                ev(5, cc(0, 6, 2)),
+                ev(5, cc(0, 121, 0)),
+                ev(5, cc(0, 11, 90)),
+                ev(10, END),
+            ]
+        );
+    }
+
+    #[test]
+    fn thin_pitch_bend() {
+        let bend = |channel: u8, value: u16| TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::PitchBend {
+                bend: crate::PitchBend(value.into()),
+            },
+        };
+        let note_off = TrackEventKind::Midi {
+            channel: 0.into(),
+            message: MidiMessage::NoteOff {
+                key: 60.into(),
+                vel: 64.into(),
+            },
+        };
+        let mut track = vec![
+            ev(0, bend(0, 8192)),
+            ev(5, bend(0, 8200)),
+            ev(5, bend(1, 8200)),
+            ev(5, bend(0, 8210)),
+            ev(5, bend(0, 8215)),
+            ev(5, note_off),
+            ev(5, bend(0, 8300)),
+            ev(5, bend(0, 8305)),
+            ev(5, END),
+        ];
+        let mut untouched = track.clone();
+        edit::thin_pitch_bend(&mut untouched, 0);
+        assert_eq!(untouched, track);
+        edit::thin_pitch_bend(&mut track, 50);
+        assert_eq!(
+            track,
+            vec![
+                ev(0, bend(0, 8192)),
+                ev(10, bend(1, 8200)),
+                ev(10, bend(0, 8215)),
+                ev(5, note_off),
+                ev(5, bend(0, 8300)),
+                ev(5, bend(0, 8305)),
+                ev(5, END),
+            ]
+        );
+    }
+
+    #[test]
+    fn map_pressure() {
+        let midi = |message| TrackEventKind::Midi {
+            channel: 0.into(),
+            message,
+        };
+        let mut track = vec![
+            ev(0, midi(MidiMessage::ChannelAftertouch { vel: 40.into() })),
+            ev(0, note_on(60)),
+            ev(
+                5,
+                midi(MidiMessage::Aftertouch {
+                    key: 60.into(),
+                    vel: 100.into(),
+                }),
+            ),
+            ev(5, END),
+        ];
+        edit::map_pressure(&mut track, |vel| {
+            crate::num::u7::new((vel.as_int() as u16 * 2).min(127) as u8)
+        });
+        assert_eq!(
+            track,
+            vec![
+                ev(0, midi(MidiMessage::ChannelAftertouch { vel: 80.into() })),
+                ev(0, note_on(60)),
+                ev(
+                    5,
+                    midi(MidiMessage::Aftertouch {
+                        key: 60.into(),
+                        vel: 127.into(),
+                    }),
+                ),
+                ev(5, END),
+            ]
+        );
+    }
+}
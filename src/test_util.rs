@@ -0,0 +1,133 @@
+//! Generators for small, valid Standard Midi Files, meant for testing code that uses `midly`.
+//!
+//! Each generator returns the raw bytes of a `.mid` file, so that tests can exercise the whole
+//! parsing path without having to ship binary fixtures:
+//!
+//! ```rust
+//! use midly::{test_util, Smf};
+//!
+//! let bytes = test_util::one_note();
+//! let smf = Smf::parse(&bytes).unwrap();
+//! assert_eq!(smf.tracks.len(), 1);
+//! ```
+//!
+//! All generated files use metrical timing with [`TICKS_PER_BEAT`](constant.TICKS_PER_BEAT.html)
+//! ticks per beat, and their exact contents are stable, so tests may rely on them.
+//!
+//! This module is only available with the `test-util` feature enabled.
+
+use crate::{
+    event::{MetaMessage, MidiMessage, TrackEvent, TrackEventKind},
+    internal::*,
+    primitive::Format,
+    smf::{Smf, Track},
+};
+
+/// The resolution of all generated files, in ticks per beat (quarter note).
+pub const TICKS_PER_BEAT: u16 = 96;
+
+/// A file with a single track, playing middle C (key 60) on channel 0 for one beat, at velocity
+/// 64.
+pub fn one_note() -> Vec<u8> {
+    let mut track = notes(0, &[60]);
+    end(&mut track);
+    write(Format::SingleTrack, vec![track])
+}
+
+/// A `Parallel` file with a conductor track followed by `tracks` tracks.
+///
+/// The conductor track holds the tempo (120 beats per minute) and a 4/4 time signature.
+/// Track `i` (counting from 1) plays a C major scale of one-beat notes on channel `i - 1`
+/// (modulo 16), starting an octave higher with each track.
+pub fn multi_track(tracks: usize) -> Vec<u8> {
+    let mut conductor = vec![
+        meta(0, MetaMessage::Tempo(u24::new(500_000))),
+        meta(0, MetaMessage::TimeSignature(4, 2, 24, 8)),
+    ];
+    end(&mut conductor);
+    let mut all = vec![conductor];
+    for i in 0..tracks {
+        let base = 36 + 12 * (i % 6) as u8;
+        let scale = [0, 2, 4, 5, 7, 9, 11, 12]
+            .iter()
+            .map(|step| base + step)
+            .collect::<Vec<_>>();
+        let mut track = notes(i as u8, &scale);
+        end(&mut track);
+        all.push(track);
+    }
+    write(Format::Parallel, all)
+}
+
+/// A file with a single track that changes tempo every beat, cycling through 60, 120 and 180
+/// beats per minute, for a total of `changes` tempo changes.
+///
+/// A note is played on every beat, so that the effect of the tempo changes can be measured.
+pub fn tempo_changes(changes: usize) -> Vec<u8> {
+    const TEMPOS: [u32; 3] = [1_000_000, 500_000, 333_333];
+    let mut track = Vec::new();
+    for i in 0..changes {
+        track.push(meta(
+            0,
+            MetaMessage::Tempo(u24::new(TEMPOS[i % TEMPOS.len()])),
+        ));
+        track.extend(notes(0, &[60 + (i % 12) as u8]));
+    }
+    end(&mut track);
+    write(Format::SingleTrack, vec![track])
+}
+
+/// Auxiliary function. Play the given keys one after the other, one beat each.
+fn notes(channel: u8, keys: &[u8]) -> Track<'static> {
+    let mut track = Vec::with_capacity(keys.len() * 2);
+    for &key in keys {
+        let key = u7::new(key);
+        track.push(midi(
+            0,
+            channel,
+            MidiMessage::NoteOn {
+                key,
+                vel: u7::new(64),
+            },
+        ));
+        track.push(midi(
+            TICKS_PER_BEAT as u32,
+            channel,
+            MidiMessage::NoteOff {
+                key,
+                vel: u7::new(64),
+            },
+        ));
+    }
+    track
+}
+
+fn midi(delta: u32, channel: u8, message: MidiMessage) -> TrackEvent<'static> {
+    TrackEvent {
+        delta: u28::new(delta),
+        kind: TrackEventKind::Midi {
+            channel: u4::new(channel % 16),
+            message,
+        },
+    }
+}
+
+fn meta(delta: u32, message: MetaMessage<'static>) -> TrackEvent<'static> {
+    TrackEvent {
+        delta: u28::new(delta),
+        kind: TrackEventKind::Meta(message),
+    }
+}
+
+fn end(track: &mut Track<'static>) {
+    track.push(meta(0, MetaMessage::EndOfTrack));
+}
+
+fn write(format: Format, tracks: Vec<Track<'static>>) -> Vec<u8> {
+    let mut smf = Smf::empty(format, TICKS_PER_BEAT);
+    smf.tracks = tracks;
+    let mut out = Vec::new();
+    smf.write(&mut out)
+        .expect("writing to a vector cannot fail with valid tracks");
+    out
+}
@@ -0,0 +1,220 @@
+//! Non-fatal issues found while parsing, which do not prevent reading a file but usually indicate
+//! an authoring bug.
+
+use crate::internal::*;
+#[cfg(feature = "alloc")]
+use crate::{
+    event::{MidiMessage, TrackEvent, TrackEventKind},
+    smf::EventBytemapIter,
+};
+
+/// A suspicious but otherwise readable construct found in a file.
+///
+/// Warnings are produced by [`Smf::parse_with_warnings`](struct.Smf.html#method.parse_with_warnings).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Warning {
+    /// The index of the track where the issue was found.
+    pub track: usize,
+    /// The index of the offending event within its track.
+    pub event: usize,
+    /// What exactly is wrong.
+    pub kind: WarningKind,
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "track {}, event {}: {}",
+            self.track, self.event, self.kind
+        )
+    }
+}
+
+/// The different kinds of [`Warning`](struct.Warning.html)s.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum WarningKind {
+    /// A note was turned on while it was already playing.
+    DuplicateNoteOn {
+        /// The channel of the note.
+        channel: u4,
+        /// The key of the note.
+        key: u7,
+    },
+    /// A note was turned off while it was not playing.
+    NoteOffWithoutNoteOn {
+        /// The channel of the note.
+        channel: u4,
+        /// The key of the note.
+        key: u7,
+    },
+    /// A SysEx message was never terminated with an `0xF7` byte.
+    ///
+    /// SysEx messages may be split into several packets: an initial `SysEx` event followed by
+    /// `Escape` events carrying the rest of the message.
+    /// This warning is raised when the last packet does not end with `0xF7`, and points at the
+    /// initial `SysEx` event.
+    UnterminatedSysEx,
+    /// A note was turned on and never turned off (a "stuck note").
+    ///
+    /// The event index of the warning points at the `NoteOn` event.
+    UnterminatedNote {
+        /// The channel of the note.
+        channel: u4,
+        /// The key of the note.
+        key: u7,
+    },
+    /// A variable-length integer was encoded with redundant leading `0x80` bytes.
+    ///
+    /// This applies both to delta times and to the lengths of meta and SysEx data.
+    /// Such integers are read just fine, but the writer always uses the shortest encoding, so the
+    /// file will not be written back byte-by-byte identical.
+    NonCanonicalVarlen,
+}
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::WarningKind::*;
+        match self {
+            DuplicateNoteOn { channel, key } => write!(
+                f,
+                "note {} on channel {} turned on while already playing",
+                key, channel
+            ),
+            NoteOffWithoutNoteOn { channel, key } => write!(
+                f,
+                "note {} on channel {} turned off while not playing",
+                key, channel
+            ),
+            UnterminatedSysEx => write!(f, "sysex message is never terminated"),
+            UnterminatedNote { channel, key } => {
+                write!(f, "note {} on channel {} is never turned off", key, channel)
+            }
+            NonCanonicalVarlen => write!(f, "variable-length integer has redundant leading bytes"),
+        }
+    }
+}
+
+/// Keep track of the on/off balance of all notes in a track, reporting any imbalances.
+#[cfg(feature = "alloc")]
+pub(crate) fn check_notes(track_idx: usize, track: &[TrackEvent], out: &mut Vec<Warning>) {
+    // The index of the `NoteOn` event that turned on each key on each channel, if any.
+    let mut playing = [[None; 128]; 16];
+    let first_warning = out.len();
+    let mut warn = |event, kind| {
+        out.push(Warning {
+            track: track_idx,
+            event,
+            kind,
+        })
+    };
+    for (idx, ev) in track.iter().enumerate() {
+        if let TrackEventKind::Midi { channel, message } = ev.kind {
+            let (key, on) = match message {
+                MidiMessage::NoteOn { key, vel } => (key, vel > 0),
+                MidiMessage::NoteOff { key, .. } => (key, false),
+                _ => continue,
+            };
+            let slot = &mut playing[channel.as_int() as usize][key.as_int() as usize];
+            match (on, slot.is_some()) {
+                (true, true) => warn(idx, WarningKind::DuplicateNoteOn { channel, key }),
+                (false, false) => warn(idx, WarningKind::NoteOffWithoutNoteOn { channel, key }),
+                _ => {}
+            }
+            *slot = if on { slot.or(Some(idx)) } else { None };
+        }
+    }
+    let mut stuck = Vec::new();
+    for (channel, keys) in playing.iter().enumerate() {
+        for (key, on_idx) in keys.iter().enumerate() {
+            if let Some(idx) = *on_idx {
+                stuck.push((
+                    idx,
+                    WarningKind::UnterminatedNote {
+                        channel: u4::new(channel as u8),
+                        key: u7::new(key as u8),
+                    },
+                ));
+            }
+        }
+    }
+    for (idx, kind) in stuck {
+        warn(idx, kind);
+    }
+    out[first_warning..].sort_by_key(|warning| warning.event);
+}
+
+/// Report SysEx messages that are never terminated by an `0xF7` byte, taking into account
+/// messages that are split into several packets.
+#[cfg(feature = "alloc")]
+pub(crate) fn check_sysex<'a, 'b: 'a>(
+    track_idx: usize,
+    track: impl IntoIterator<Item = &'a TrackEvent<'b>>,
+    out: &mut Vec<Warning>,
+) {
+    // The index of the `SysEx` event that started the message currently being continued, if any.
+    let mut open = None;
+    let mut warn = |event| {
+        out.push(Warning {
+            track: track_idx,
+            event,
+            kind: WarningKind::UnterminatedSysEx,
+        })
+    };
+    for (idx, ev) in track.into_iter().enumerate() {
+        match ev.kind {
+            TrackEventKind::SysEx(data) => {
+                // A new message while the previous one was still open
+                if let Some(start) = open {
+                    warn(start);
+                }
+                open = if data.last() == Some(&0xF7) {
+                    None
+                } else {
+                    Some(idx)
+                };
+            }
+            // The last packet of a split message
+            TrackEventKind::Escape(data) if open.is_some() && data.last() == Some(&0xF7) => {
+                open = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = open {
+        warn(start);
+    }
+}
+
+/// Report events whose delta time or data length is encoded with more bytes than necessary.
+#[cfg(feature = "alloc")]
+pub(crate) fn check_varlen(track_idx: usize, mut events: EventBytemapIter, out: &mut Vec<Warning>) {
+    let varlen_len = |int: u28| {
+        let mut counter = WriteCounter(0);
+        let _ = int.write_varlen(&mut counter);
+        counter.0 as usize
+    };
+    let encoded_len = |ev: &TrackEvent| {
+        let mut counter = WriteCounter(0);
+        let _ = ev.write(&mut None, &mut counter);
+        counter.0 as usize
+    };
+    let mut idx = 0;
+    loop {
+        let before = events.unread().len();
+        let (bytes, ev) = match events.next() {
+            Some(Ok(ev)) => ev,
+            _ => break,
+        };
+        let consumed = before - events.unread().len();
+        // Running status can only make the event shorter than its encoding with explicit status
+        let delta_len = varlen_len(ev.delta);
+        let event_len = encoded_len(&ev) - delta_len;
+        if consumed - bytes.len() > delta_len || bytes.len() > event_len {
+            out.push(Warning {
+                track: track_idx,
+                event: idx,
+                kind: WarningKind::NonCanonicalVarlen,
+            });
+        }
+        idx += 1;
+    }
+}